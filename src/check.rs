@@ -0,0 +1,194 @@
+//! `--check`, which makes sure the mouse and keyboard events Eclipse sends are still what receivers
+//! built against stardust-xr-molecules expect, since a mismatch only shows as receivers getting
+//! nothing.
+
+use crate::events::{mask_fields, timestamp, EventBatch, KeyboardInput, Modifiers, MouseInput};
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::client::Client;
+use stardust_xr_fusion::core::schemas::flex::flexbuffers;
+use stardust_xr_fusion::core::values::Transform;
+use stardust_xr_fusion::data::PulseSender;
+use stardust_xr_fusion::node::NodeError;
+use stardust_xr_molecules::keyboard::{KeyboardEvent, KEYBOARD_MASK};
+use stardust_xr_molecules::mouse::{MouseEvent, MOUSE_MASK};
+
+/// Print the mouse and keyboard masks and check that an event of each matches its mask and reads
+/// back as the molecules event receivers parse it into, failing if anything doesn't.
+pub async fn run() -> Result<()> {
+    let mouse = MouseInput::from(MouseEvent::new(
+        Some([1.0, -2.0].into()),
+        Some([0.0, 3.5].into()),
+        Some([0.0, 1.0].into()),
+        Some(vec![input_event_codes::BTN_LEFT!()]),
+        Some(vec![input_event_codes::BTN_RIGHT!()]),
+    ));
+    let keyboard = KeyboardInput {
+        event: KeyboardEvent {
+            keyboard: "xkbv1".to_string(),
+            keymap: None,
+            keys_up: Some(vec![input_event_codes::KEY_A!()]),
+            keys_down: Some(vec![input_event_codes::KEY_S!()]),
+        },
+        modifiers: Modifiers::default(),
+        repeat: false,
+        keysyms: Vec::new(),
+        keysym_names: Vec::new(),
+        text: None,
+        keymap_index: 0,
+        seat: "seat0".to_string(),
+        device: None,
+        time: timestamp(),
+    };
+    let results = [
+        create_senders().await,
+        check(
+            "mouse",
+            &MOUSE_MASK,
+            &mouse,
+            &mouse.event,
+            MouseEvent::from_pulse_data,
+        ),
+        check(
+            "keyboard",
+            &KEYBOARD_MASK,
+            &keyboard,
+            &keyboard.event,
+            KeyboardEvent::from_pulse_data,
+        ),
+        check_batch("mouse", &MOUSE_MASK, &mouse),
+        check_batch("keyboard", &KEYBOARD_MASK, &keyboard),
+    ];
+    let failures = results.into_iter().filter(|ok| !ok).count();
+    if failures > 0 {
+        return Err(eyre!("{failures} checks failed"));
+    }
+    println!("all checks passed");
+    Ok(())
+}
+
+/// Create a mouse and a keyboard pulse sender on the server if there is one, to see it takes the
+/// masks. Not being able to connect only skips this.
+async fn create_senders() -> bool {
+    let (client, _event_loop) = match Client::connect_with_async_loop().await {
+        Ok(connection) => connection,
+        Err(err) => {
+            println!("skipping creating pulse senders, couldn't connect to the server: {err}");
+            return true;
+        }
+    };
+    let root = client.get_root();
+    let created = async {
+        let _mouse = PulseSender::create(root, Transform::identity(), &MOUSE_MASK)?;
+        let _keyboard = PulseSender::create(root, Transform::identity(), &KEYBOARD_MASK)?;
+        // the server handles messages in order, so this is only answered once it has the senders
+        root.get_position_rotation_scale(root)?.await?;
+        Ok::<_, NodeError>(())
+    }
+    .await;
+    match created {
+        Ok(()) => {
+            println!("created mouse and keyboard pulse senders");
+            true
+        }
+        Err(err) => {
+            println!("couldn't create pulse senders: {err}");
+            false
+        }
+    }
+}
+
+/// Print `mask` and check that `event` serialized like it's sent matches it and reads back with
+/// `parse` as the same thing as `expected`.
+fn check<E: Serialize, M: Serialize>(
+    kind: &str,
+    mask: &[u8],
+    event: &E,
+    expected: &M,
+    parse: fn(&[u8]) -> Option<M>,
+) -> bool {
+    let Some(mask) = to_json(mask) else {
+        println!("{kind}: the mask isn't a flexbuffer");
+        return false;
+    };
+    println!("{kind} mask: {mask}");
+    let mut serializer = flexbuffers::FlexbufferSerializer::new();
+    if let Err(err) = event.serialize(&mut serializer) {
+        println!("{kind}: couldn't serialize an event: {err}");
+        return false;
+    }
+    let data = serializer.take_buffer();
+    let Some(sent) = to_json(&data) else {
+        println!("{kind}: a serialized event isn't a flexbuffer");
+        return false;
+    };
+    let mut ok = matches_mask(kind, "events", &mask, &sent);
+    match parse(&data) {
+        Some(parsed) if to_value(&parsed) == to_value(expected) => {
+            println!("{kind}: events read back the same as they were sent");
+        }
+        Some(parsed) => {
+            println!(
+                "{kind}: events read back as {} instead of {}",
+                to_value(&parsed),
+                to_value(expected)
+            );
+            ok = false;
+        }
+        None => {
+            println!("{kind}: events don't read back as what receivers expect");
+            ok = false;
+        }
+    }
+    ok
+}
+
+/// Check that a batch of `event` serialized like it's sent with `batch_events` matches `mask` too.
+fn check_batch<E: Serialize>(kind: &str, mask: &[u8], event: &E) -> bool {
+    let fields = mask_fields(mask);
+    let batch = EventBatch {
+        mask: &fields,
+        events: std::slice::from_ref(event),
+    };
+    let mut serializer = flexbuffers::FlexbufferSerializer::new();
+    if let Err(err) = batch.serialize(&mut serializer) {
+        println!("{kind}: couldn't serialize a batch: {err}");
+        return false;
+    }
+    let (Some(mask), Some(sent)) = (to_json(mask), to_json(&serializer.take_buffer())) else {
+        println!("{kind}: a serialized batch isn't a flexbuffer");
+        return false;
+    };
+    let ok = matches_mask(kind, "batches", &mask, &sent);
+    if ok {
+        println!("{kind}: batches match the mask");
+    }
+    ok
+}
+
+/// Whether `sent` has every key and value `mask` has, since receivers are only connected to
+/// senders with those, printing the ones it doesn't.
+fn matches_mask(
+    kind: &str,
+    what: &str,
+    mask: &serde_json::Value,
+    sent: &serde_json::Value,
+) -> bool {
+    let mut ok = true;
+    for (key, value) in mask.as_object().into_iter().flatten() {
+        if sent.get(key) != Some(value) {
+            println!("{kind}: {what} don't have {key:?} set to {value} like the mask");
+            ok = false;
+        }
+    }
+    ok
+}
+
+fn to_json(flexbuffer: &[u8]) -> Option<serde_json::Value> {
+    let root = flexbuffers::Reader::get_root(flexbuffer).ok()?;
+    serde_json::Value::deserialize(root).ok()
+}
+
+fn to_value(value: &impl Serialize) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
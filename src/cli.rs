@@ -0,0 +1,75 @@
+//! Command line arguments, which take priority over the config file.
+
+use crate::config::Config;
+use clap::Parser;
+use color_eyre::eyre::Result;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Parser)]
+#[command(author, version, about)]
+pub struct Args {
+    /// Config file to use instead of eclipse.toml in the XDG config directory
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Seat to take input devices from
+    #[arg(long)]
+    pub seat: Option<String>,
+    /// XKB keyboard layout, e.g. "us" or "de"
+    #[arg(long)]
+    pub layout: Option<String>,
+    /// Scroll in the traditional direction even if the config or the device's defaults enable
+    /// natural scrolling
+    #[arg(long)]
+    pub no_natural_scroll: bool,
+    /// Log filter such as "debug" or "eclipse=trace", overriding RUST_LOG
+    #[arg(long, value_name = "FILTER")]
+    pub log_level: Option<String>,
+    /// Print the input devices on the seat and exit
+    #[arg(long)]
+    pub list_devices: bool,
+    /// Check that the mouse and keyboard events Eclipse sends match what receivers expect and exit,
+    /// failing if they don't
+    #[arg(long)]
+    pub check: bool,
+    /// Write the mouse and keyboard events Eclipse receives to this file, to reproduce problems with
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+    /// Play back a recording made with --record instead of reading any input devices
+    #[arg(long, value_name = "PATH", conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+    /// How many times as fast as it was recorded to play back the recording
+    #[arg(
+        long,
+        value_name = "FACTOR",
+        default_value_t = 1.0,
+        requires = "replay"
+    )]
+    pub replay_speed: f64,
+    /// Read input and log every event without connecting to a server or sending anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+impl Args {
+    /// Load the config file with the command line applied on top of it.
+    pub fn load_config(&self) -> Result<Config> {
+        let mut config = match self.config.clone().or_else(Config::default_path) {
+            Some(path) => Config::load(&path)?,
+            None => Config::default(),
+        };
+        self.apply(&mut config);
+        Ok(config)
+    }
+
+    /// Override the parts of `config` that were given on the command line.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(seat) = &self.seat {
+            config.seat.clone_from(seat);
+        }
+        if let Some(layout) = &self.layout {
+            config.keymap.xkb_layout.clone_from(layout);
+        }
+        if self.no_natural_scroll {
+            config.scroll.traditional = true;
+        }
+    }
+}
@@ -0,0 +1,997 @@
+//! Settings for how Eclipse reads and translates input.
+
+use crate::error::EclipseError;
+use crate::metrics::InputKind;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Everything that can be set in `eclipse.toml`, any missing values use their defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The seat to take input devices from.
+    pub seat: String,
+    /// More seats to take input devices from at the same time, each with keyboard state of its own.
+    pub extra_seats: Vec<SeatConfig>,
+    /// Use exactly these devices (like `/dev/input/event3`) instead of the seat's, without needing
+    /// udev. Devices plugged in later aren't picked up.
+    pub device_paths: Vec<PathBuf>,
+    pub broadcast: BroadcastMode,
+    pub timestamps: Timestamps,
+    /// Size in pixels of the surface absolute pointing devices (touchscreens, tablets) are mapped onto.
+    pub absolute_surface_size: [u32; 2],
+    pub keymap: KeymapConfig,
+    /// Keymaps the `next_keymap` hotkey switches to in turn after `keymap`.
+    pub extra_keymaps: Vec<KeymapConfig>,
+    pub key_repeat: KeyRepeatConfig,
+    pub scroll: ScrollConfig,
+    pub pointer: PointerConfig,
+    pub touchpad: TouchpadConfig,
+    pub tablet: TabletConfig,
+    pub absolute_pointer: AbsolutePointerConfig,
+    pub devices: DeviceFilterConfig,
+    /// Settings for single devices, by name, sysname (like `event5`) or `vendor:product` ID.
+    pub device: HashMap<String, DeviceConfig>,
+    pub hotkeys: HotkeyConfig,
+    pub gamepad: GamepadConfig,
+    pub capture: CaptureConfig,
+    pub cursor: CursorConfig,
+    pub clicks: ClickConfig,
+    pub drag: DragConfig,
+    pub button_repeat: ButtonRepeatConfig,
+    pub channel_capacity: ChannelCapacityConfig,
+    pub forward: ForwardConfig,
+    /// Send all of a frame's mouse and keyboard events to each receiver as one pulse with an `events`
+    /// list, for receivers that understand batches.
+    pub batch_events: bool,
+    /// Seconds between logging event throughput metrics, 0 to never log them.
+    pub metrics_interval: u64,
+    /// Seconds between logging that Eclipse is still alive and how many receivers it has, 0 to
+    /// never log it.
+    pub heartbeat_interval: u64,
+    /// Only forward input while Eclipse's logind session is the active one on its seat.
+    pub active_session_only: bool,
+    /// Seconds without any input before receivers are told it's idle, 0 to never tell them.
+    pub idle_timeout: u64,
+    /// Times per second queued events are also sent between frames, so input doesn't lag behind
+    /// a server rendering slowly. 0 sends them only once per frame.
+    pub flush_rate: u32,
+    /// Send button names like `Left` along with the raw evdev codes in mouse events.
+    pub button_names: bool,
+    /// Mark middle button presses in mouse events as pasting the primary selection.
+    pub middle_click_paste: bool,
+    /// Tell mouse receivers when the pointer focus moves onto and off them, for hover highlights.
+    pub hover_events: bool,
+    /// Also send what printable key presses type as text events, for receivers that only want text
+    /// and not a whole keymap.
+    pub text_events: bool,
+    /// Key combinations like `Ctrl+Z` mouse buttons type instead of being pressed, by button name
+    /// like `Side` or evdev code.
+    pub button_keys: HashMap<String, String>,
+    /// Keys that are never sent to receivers, by keysym name like `Super_L` or evdev code. They
+    /// still work in hotkeys.
+    pub inhibited_keys: Vec<String>,
+    /// UID of the receiver input goes to whenever it's connected, so routing doesn't depend on the
+    /// order receivers connect in.
+    pub preferred_receiver: Option<String>,
+    /// Unix socket to take JSON commands on, one per line. Without one there's no control interface.
+    pub control_socket: Option<PathBuf>,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seat: "seat0".to_string(),
+            extra_seats: Vec::new(),
+            device_paths: Vec::new(),
+            broadcast: BroadcastMode::default(),
+            timestamps: Timestamps::default(),
+            absolute_surface_size: [1920, 1080],
+            keymap: KeymapConfig::default(),
+            extra_keymaps: Vec::new(),
+            key_repeat: KeyRepeatConfig::default(),
+            scroll: ScrollConfig::default(),
+            pointer: PointerConfig::default(),
+            touchpad: TouchpadConfig::default(),
+            devices: DeviceFilterConfig::default(),
+            device: HashMap::new(),
+            hotkeys: HotkeyConfig::default(),
+            gamepad: GamepadConfig::default(),
+            tablet: TabletConfig::default(),
+            absolute_pointer: AbsolutePointerConfig::default(),
+            capture: CaptureConfig::default(),
+            cursor: CursorConfig::default(),
+            clicks: ClickConfig::default(),
+            drag: DragConfig::default(),
+            button_repeat: ButtonRepeatConfig::default(),
+            channel_capacity: ChannelCapacityConfig::default(),
+            forward: ForwardConfig::default(),
+            batch_events: false,
+            metrics_interval: 0,
+            heartbeat_interval: 0,
+            active_session_only: true,
+            idle_timeout: 0,
+            flush_rate: 0,
+            button_names: false,
+            middle_click_paste: false,
+            hover_events: false,
+            text_events: false,
+            button_keys: HashMap::new(),
+            inhibited_keys: Vec::new(),
+            preferred_receiver: None,
+            control_socket: None,
+        }
+    }
+}
+impl Config {
+    /// `eclipse.toml` in the XDG config directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("eclipse.toml"))
+    }
+
+    /// The config for each seat in use, starting with `seat`. There's only `seat` when devices are
+    /// opened by path.
+    pub fn seat_configs(&self) -> Vec<Config> {
+        let extra_seats = if self.device_paths.is_empty() {
+            &self.extra_seats[..]
+        } else {
+            &[]
+        };
+        std::iter::once(self.clone())
+            .chain(extra_seats.iter().map(|seat| self.for_seat(&seat.name)))
+            .collect()
+    }
+
+    /// This config as it applies to `seat`, which can have a keymap of its own.
+    pub fn for_seat(&self, seat: &str) -> Config {
+        let mut config = self.clone();
+        if let Some(extra_seat) = self.extra_seats.iter().find(|extra| extra.name == seat) {
+            config.seat.clone_from(&extra_seat.name);
+            if let Some(keymap) = &extra_seat.keymap {
+                config.keymap.clone_from(keymap);
+            }
+        }
+        config
+    }
+
+    /// The settings for the device with this name, sysname or ID, if there are any.
+    pub fn device_config(
+        &self,
+        name: &str,
+        sysname: &str,
+        vendor: u32,
+        product: u32,
+    ) -> Option<&DeviceConfig> {
+        let id = format!("{vendor:04x}:{product:04x}");
+        self.device
+            .get(name)
+            .or_else(|| self.device.get(sysname))
+            .or_else(|| {
+                self.device
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(&id))
+                    .map(|(_, config)| config)
+            })
+    }
+
+    /// Load the config file at `path`, or the defaults if there isn't one.
+    pub fn load(path: &Path) -> Result<Self, EclipseError> {
+        match std::fs::read_to_string(path) {
+            Ok(config) => toml::from_str(&config).map_err(|source| EclipseError::ParseConfig {
+                path: path.to_path_buf(),
+                source,
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(source) => Err(EclipseError::ReadConfig {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+}
+
+/// How each input event is distributed among the connected receivers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BroadcastMode {
+    /// Send each event to a single receiver.
+    #[default]
+    Off,
+    /// Mirror each event to every connected receiver.
+    On,
+}
+
+/// Where the `time` of mouse and keyboard events comes from, microseconds on the monotonic clock
+/// either way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Timestamps {
+    /// When the device reported the input according to libinput, for receivers predicting motion
+    /// or measuring latency. Events Eclipse makes up itself, like key repeats, get the time they're
+    /// made at.
+    #[default]
+    Libinput,
+    /// When Eclipse got the event from libinput.
+    Received,
+}
+
+/// The XKB RMLVO names the keymap is compiled from.
+///
+/// Empty names are filled in by xkbcommon from the `XKB_DEFAULT_*` environment variables, falling
+/// back to its own defaults (usually a US layout).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub xkb_rules: String,
+    pub xkb_model: String,
+    pub xkb_layout: String,
+    pub xkb_variant: String,
+    pub xkb_options: Option<String>,
+}
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        KeymapConfig {
+            xkb_rules: "evdev".to_string(),
+            xkb_model: String::new(),
+            xkb_layout: String::new(),
+            xkb_variant: String::new(),
+            xkb_options: None,
+        }
+    }
+}
+
+/// How held keys repeat.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct KeyRepeatConfig {
+    /// Milliseconds a key has to be held before it starts repeating.
+    pub delay: u64,
+    /// Repeats per second once repeating, 0 disables key repeat.
+    pub rate: f32,
+}
+impl Default for KeyRepeatConfig {
+    fn default() -> Self {
+        KeyRepeatConfig {
+            delay: 500,
+            rate: 30.0,
+        }
+    }
+}
+
+/// How scroll wheels and touchpad scrolling translate into scroll deltas.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScrollConfig {
+    /// Scroll content along with the fingers instead of the scrollbar, on both axes. Devices
+    /// libinput scrolls naturally by default do anyway, and `natural_scroll` in a device's config
+    /// overrides both.
+    pub natural: bool,
+    /// Scroll every device in the traditional direction whatever `natural`, its own config or
+    /// libinput says, for `--no-natural-scroll`.
+    #[serde(skip)]
+    pub traditional: bool,
+    /// Factor every scroll delta is scaled by.
+    pub multiplier: f32,
+    /// Factors the device's horizontal and vertical scrolling are scaled by on top of `multiplier`,
+    /// before `horizontal_modifier` turns one into the other. 0 ignores that axis and a negative
+    /// one flips its direction.
+    pub horizontal_multiplier: f32,
+    pub vertical_multiplier: f32,
+    /// Only send scroll wheel steps once they add up to a whole detent, carrying fractions over to
+    /// the next ones. Touchpad and other continuous scrolling isn't affected.
+    pub whole_detents: bool,
+    /// What scroll wheel events carry, see `WheelUnits`.
+    pub wheel_units: WheelUnits,
+    /// Modifier (Shift, Ctrl, Alt or Super) that turns vertical scrolling horizontal while it's held,
+    /// for mice without a horizontal wheel. Empty to disable.
+    pub horizontal_modifier: String,
+    /// How much of the wheel's `scroll_value` makes a detent, for older devices libinput only
+    /// reports the legacy wheel axes of and a v120 value of zero for. 15 degrees on most mice.
+    pub legacy_wheel_step: f32,
+    /// How far a touchpad or other continuous scroll has to go along one axis before the other one
+    /// is ignored until it ends, so scrolling straight doesn't drift sideways. 0 disables.
+    pub axis_lock: f32,
+    pub momentum: MomentumConfig,
+    pub keys: ScrollKeysConfig,
+}
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        ScrollConfig {
+            natural: false,
+            traditional: false,
+            multiplier: 1.0,
+            horizontal_multiplier: 1.0,
+            vertical_multiplier: 1.0,
+            whole_detents: false,
+            wheel_units: WheelUnits::default(),
+            horizontal_modifier: String::new(),
+            legacy_wheel_step: 15.0,
+            axis_lock: 0.0,
+            momentum: MomentumConfig::default(),
+            keys: ScrollKeysConfig::default(),
+        }
+    }
+}
+impl ScrollConfig {
+    /// Apply the speed settings and the direction of the device the raw scroll delta is from.
+    pub fn apply(&self, delta: glam::Vec2, natural: bool) -> glam::Vec2 {
+        let direction = if natural { -1.0 } else { 1.0 };
+        let axes = glam::vec2(self.horizontal_multiplier, self.vertical_multiplier);
+        delta * direction * self.multiplier * axes
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WheelUnits {
+    /// `scroll_steps` in detents, where an eighth of a detent from a high resolution wheel is 0.125.
+    #[default]
+    Detents,
+    /// `scroll_v120` with the wheel's raw integer values, where 120 is one detent. `whole_detents`
+    /// doesn't apply and the multiplier is rounded away if it doesn't give whole values.
+    V120,
+}
+
+/// Finger and continuous scrolling carrying on by itself after it's let go, slowing down until it stops.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MomentumConfig {
+    pub enabled: bool,
+    /// How quickly the scrolling slows down, the fraction of its speed lost per second is
+    /// `1 - e^-friction`.
+    pub friction: f32,
+    /// Milliseconds of scrolling before it's let go that its speed is measured over.
+    pub window: u64,
+    /// Speed in scroll units per second below which scrolling stops, or doesn't carry on at all.
+    pub min_speed: f32,
+}
+impl Default for MomentumConfig {
+    fn default() -> Self {
+        MomentumConfig {
+            enabled: false,
+            friction: 4.0,
+            window: 100,
+            min_speed: 10.0,
+        }
+    }
+}
+
+/// Typing keys like the arrow keys instead of scrolling, for receivers that don't understand scroll
+/// events.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScrollKeysConfig {
+    pub enabled: bool,
+    /// Key combinations typed for every step scrolled in each direction, like `Up` or `Page_Down`.
+    /// Empty to keep scrolling in that direction.
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    /// How far a touchpad or other continuous scroll has to go for a key press, a wheel types one
+    /// for every detent.
+    pub finger_distance: f32,
+}
+impl Default for ScrollKeysConfig {
+    fn default() -> Self {
+        ScrollKeysConfig {
+            enabled: false,
+            up: "Up".to_string(),
+            down: "Down".to_string(),
+            left: "Left".to_string(),
+            right: "Right".to_string(),
+            finger_distance: 15.0,
+        }
+    }
+}
+
+/// A seat used alongside the main one.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SeatConfig {
+    pub name: String,
+    /// Keymap for the seat's keyboards instead of the main `keymap`.
+    pub keymap: Option<KeymapConfig>,
+}
+
+/// How relative pointer motion is scaled before it's sent.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct PointerConfig {
+    pub accel_profile: AccelProfile,
+    /// Factor pointer motion is scaled by after acceleration, below 1 slows down high DPI mice.
+    pub sensitivity: f32,
+    /// Swap the primary and secondary mouse buttons.
+    pub left_handed: bool,
+    /// Relative motion shorter than this, after acceleration and sensitivity, is held back until
+    /// more motion makes it go further, so sensor jitter doesn't creep the pointer. 0 disables.
+    pub dead_zone: f32,
+}
+impl Default for PointerConfig {
+    fn default() -> Self {
+        PointerConfig {
+            accel_profile: AccelProfile::default(),
+            sensitivity: 1.0,
+            left_handed: false,
+            dead_zone: 0.0,
+        }
+    }
+}
+impl PointerConfig {
+    /// Pick the delta matching the acceleration profile and scale it by the sensitivity.
+    pub fn apply(&self, accelerated: glam::Vec2, unaccelerated: glam::Vec2) -> glam::Vec2 {
+        let delta = match self.accel_profile {
+            AccelProfile::Flat => unaccelerated,
+            AccelProfile::Adaptive => accelerated,
+        };
+        delta * self.sensitivity
+    }
+
+    /// Map from the evdev button codes the device reports to the ones that should be sent instead.
+    pub fn button_remap(&self) -> HashMap<u32, u32> {
+        let mut remap = HashMap::new();
+        if self.left_handed {
+            remap.extend(LEFT_HANDED_BUTTONS.iter().copied());
+        }
+        remap
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TouchpadConfig {
+    /// Ignore touchpad motion and clicks right after typing, so palms resting on it don't move the
+    /// pointer.
+    pub disable_while_typing: bool,
+    /// Milliseconds after the last key press (other than modifiers) that the touchpad stays ignored.
+    pub typing_timeout: u64,
+    /// Click by tapping instead of having to press the touchpad down.
+    pub tap_to_click: bool,
+    /// Tap and then touch again straight away to drag, for as long as the finger stays down.
+    pub tap_and_drag: bool,
+    /// Which buttons tapping with one, two and three fingers clicks.
+    pub tap_button_map: TapButtonMap,
+}
+impl Default for TouchpadConfig {
+    fn default() -> Self {
+        TouchpadConfig {
+            disable_while_typing: false,
+            typing_timeout: 300,
+            tap_to_click: true,
+            tap_and_drag: true,
+            tap_button_map: TapButtonMap::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TapButtonMap {
+    /// Left, right, middle.
+    #[default]
+    Lrm,
+    /// Left, middle, right.
+    Lmr,
+}
+
+/// Button remaps for left-handed mode.
+const LEFT_HANDED_BUTTONS: &[(u32, u32)] = &[
+    (
+        input_event_codes::BTN_LEFT!(),
+        input_event_codes::BTN_RIGHT!(),
+    ),
+    (
+        input_event_codes::BTN_RIGHT!(),
+        input_event_codes::BTN_LEFT!(),
+    ),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelProfile {
+    /// Motion maps 1:1 to device movement no matter how fast it is.
+    Flat,
+    /// Faster movement goes further, using the device's libinput acceleration curve.
+    #[default]
+    Adaptive,
+}
+
+/// Which input devices get used.
+///
+/// Entries are either a device name as printed by `--list-devices` or its `vendor:product` ID in hex.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DeviceFilterConfig {
+    /// Only use these devices, or every device if empty.
+    pub allow: Vec<String>,
+    /// Never use these devices, even if they're allowed.
+    pub deny: Vec<String>,
+}
+impl DeviceFilterConfig {
+    pub fn accepts(&self, name: &str, vendor: u32, product: u32) -> bool {
+        let id = format!("{vendor:04x}:{product:04x}");
+        let matches = |entry: &String| entry == name || entry.eq_ignore_ascii_case(&id);
+        (self.allow.is_empty() || self.allow.iter().any(matches)) && !self.deny.iter().any(matches)
+    }
+}
+
+/// Settings for a single device, anything left out uses the global setting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    /// libinput's acceleration speed from -1 (slowest) to 1 (fastest). This and the profile only
+    /// matter while `pointer.accel_profile` is adaptive, since flat skips libinput's acceleration.
+    pub accel_speed: Option<f64>,
+    pub accel_profile: Option<AccelProfile>,
+    pub natural_scroll: Option<bool>,
+    pub tap_to_click: Option<bool>,
+    pub send_events: Option<SendEvents>,
+}
+
+/// When a device sends events at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendEvents {
+    Enabled,
+    Disabled,
+    /// Only while no external mouse is plugged in, for touchpads.
+    DisabledOnExternalMouse,
+}
+
+/// Key combinations Eclipse handles itself instead of sending them on, written like `Ctrl+Alt+F1`.
+///
+/// The modifiers are Shift, Ctrl, Alt and Super, and the key is an xkb keysym name. Set a hotkey to
+/// an empty string to disable it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Move focus to the next receiver.
+    pub focus_next: String,
+    /// Capture input for the focused receivers or release it again, unset by default.
+    pub toggle_capture: String,
+    /// Switch to the next of `keymap` and `extra_keymaps`, unset by default.
+    pub next_keymap: String,
+    /// Stop sending input to receivers or start again, unset by default.
+    pub toggle_pause: String,
+    /// Shut down the same way as with Ctrl+C, unset by default.
+    pub quit: String,
+}
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        HotkeyConfig {
+            focus_next: "Super+Tab".to_string(),
+            toggle_capture: String::new(),
+            next_keymap: String::new(),
+            toggle_pause: String::new(),
+            quit: String::new(),
+        }
+    }
+}
+
+/// What changes while input is captured with the `toggle_capture` hotkey.
+///
+/// Captured input stays with the focused receivers like a game's pointer lock: focus doesn't follow
+/// the pointer or clicks, and only relative motion is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Only send to the focused receivers while captured even if broadcasting is on.
+    pub exclusive: bool,
+}
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig { exclusive: true }
+    }
+}
+
+/// A pointer position Eclipse adds relative motion up into, so receivers can draw a cursor without
+/// each of them doing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CursorConfig {
+    /// Send where the cursor is with every mouse event.
+    pub enabled: bool,
+    /// Size in pixels of the area the cursor stays in. Absolute pointing devices put it where they
+    /// point on `absolute_surface_size`, so the two are usually the same.
+    pub bounds: [u32; 2],
+    pub edges: CursorEdges,
+    /// Put the cursor back in the middle of `bounds` whenever the pointer focus moves to another
+    /// receiver. That's the same spot whichever receiver it is, Eclipse doesn't know the size of
+    /// their fields.
+    pub reset_on_focus: bool,
+}
+impl Default for CursorConfig {
+    fn default() -> Self {
+        CursorConfig {
+            enabled: false,
+            bounds: [1920, 1080],
+            edges: CursorEdges::default(),
+            reset_on_focus: false,
+        }
+    }
+}
+
+/// Counting clicks, so receivers don't each have to tell double clicks from two single ones.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ClickConfig {
+    /// Send `click_count` with every button press.
+    pub enabled: bool,
+    /// Most milliseconds between presses of the same button that still count as clicking again.
+    pub interval: u64,
+    /// Most pixels the pointer can move between presses that still count as clicking again.
+    pub distance: f32,
+}
+impl Default for ClickConfig {
+    fn default() -> Self {
+        ClickConfig {
+            enabled: false,
+            interval: 400,
+            distance: 4.0,
+        }
+    }
+}
+
+/// Telling drags from clicks, so receivers don't each need their own threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DragConfig {
+    /// Send `drag` when a drag starts and ends.
+    pub enabled: bool,
+    /// Pixels the pointer has to move with a button held for it to be a drag.
+    pub threshold: f32,
+}
+impl Default for DragConfig {
+    fn default() -> Self {
+        DragConfig {
+            enabled: false,
+            threshold: 4.0,
+        }
+    }
+}
+
+/// Clicking mouse buttons over and over while they're held, like held keys repeat.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ButtonRepeatConfig {
+    /// The buttons that repeat, by name like `Left` or evdev code. None do by default.
+    pub buttons: Vec<String>,
+    /// Milliseconds a button has to be held before it starts repeating.
+    pub delay: u64,
+    /// Clicks per second once repeating.
+    pub rate: f32,
+}
+impl Default for ButtonRepeatConfig {
+    fn default() -> Self {
+        ButtonRepeatConfig {
+            buttons: Vec::new(),
+            delay: 500,
+            rate: 10.0,
+        }
+    }
+}
+
+/// Which part of a tablet is mapped onto the surface absolute devices are mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TabletConfig {
+    /// Top left corner of the mapped area, as fractions of the tablet's width and height.
+    pub area_offset: [f32; 2],
+    /// Size of the mapped area, as fractions of the tablet's width and height.
+    pub area_size: [f32; 2],
+    /// Shrink the mapped area around its center to the surface's aspect ratio, so circles drawn on
+    /// the tablet don't come out as ellipses.
+    pub lock_aspect_ratio: bool,
+}
+impl Default for TabletConfig {
+    fn default() -> Self {
+        TabletConfig {
+            area_offset: [0.0, 0.0],
+            area_size: [1.0, 1.0],
+            lock_aspect_ratio: false,
+        }
+    }
+}
+impl TabletConfig {
+    /// Map a position on the tablet from 0 to 1 on both axes into the mapped area, also from 0 to
+    /// 1, along with whether it was outside the area and got clamped into it.
+    ///
+    /// `tablet_size` is the tablet's physical size, without which the aspect ratio can't be locked.
+    pub fn map(
+        &self,
+        position: glam::Vec2,
+        tablet_size: Option<glam::Vec2>,
+        surface_size: glam::Vec2,
+    ) -> (glam::Vec2, bool) {
+        let mut offset = glam::Vec2::from(self.area_offset);
+        let mut size = glam::Vec2::from(self.area_size).max(glam::Vec2::splat(f32::EPSILON));
+        let tablet_size = tablet_size.filter(|size| size.min_element() > 0.0);
+        if let Some(tablet_size) = tablet_size.filter(|_| self.lock_aspect_ratio) {
+            let area = size * tablet_size;
+            let aspect_ratio = surface_size.x / surface_size.y.max(1.0);
+            let fitted = if area.x / area.y > aspect_ratio {
+                glam::vec2(area.y * aspect_ratio, area.y)
+            } else {
+                glam::vec2(area.x, area.x / aspect_ratio)
+            } / tablet_size;
+            offset += (size - fitted) / 2.0;
+            size = fitted;
+        }
+        let mapped = (position - offset) / size;
+        let clamped = mapped.clamp(glam::Vec2::ZERO, glam::Vec2::ONE);
+        (clamped, clamped != mapped)
+    }
+}
+
+/// Which part of the surface absolute pointers, like touchscreens acting as a mouse or a VM's
+/// pointer, are mapped onto.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AbsolutePointerConfig {
+    /// Top left corner of the area, as fractions of the surface's width and height.
+    pub area_offset: [f32; 2],
+    /// Size of the area, as fractions of the surface's width and height.
+    pub area_size: [f32; 2],
+}
+impl Default for AbsolutePointerConfig {
+    fn default() -> Self {
+        AbsolutePointerConfig {
+            area_offset: [0.0, 0.0],
+            area_size: [1.0, 1.0],
+        }
+    }
+}
+impl AbsolutePointerConfig {
+    /// Map a position on the device from 0 to 1 on both axes to pixels on the surface within the
+    /// area, along with the position from 0 to 1 within the area. Positions past the device's edges
+    /// are clamped to them rather than ending up outside the area.
+    pub fn map(&self, position: glam::Vec2, surface_size: glam::Vec2) -> (glam::Vec2, glam::Vec2) {
+        let normalized = position.clamp(glam::Vec2::ZERO, glam::Vec2::ONE);
+        let offset = glam::Vec2::from(self.area_offset);
+        let size = glam::Vec2::from(self.area_size);
+        ((offset + normalized * size) * surface_size, normalized)
+    }
+}
+
+/// How many events the channels from the libinput threads hold before the threads have to wait for
+/// the next frame to take some out.
+///
+/// Nothing is dropped when they're full. Motion is merged into a single pending event until there's
+/// space again, so a bigger mouse channel queues up more motion for slow frames to catch up on
+/// instead of sending it merged, trading latency for detail. Everything else waits for space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ChannelCapacityConfig {
+    pub mouse: usize,
+    pub keyboard: usize,
+}
+impl Default for ChannelCapacityConfig {
+    fn default() -> Self {
+        ChannelCapacityConfig {
+            mouse: 256,
+            keyboard: 256,
+        }
+    }
+}
+
+/// The kinds of input Eclipse forwards. Input of the others is ignored as it's read, and there's no
+/// channel or pulse sender for it, so receivers for it never see Eclipse at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ForwardConfig {
+    /// Key presses, which the hotkeys and key repeat need too.
+    pub keyboard: bool,
+    /// Mice and touchpads, which click to focus and the cursor need too.
+    pub pointer: bool,
+    pub touch: bool,
+    /// Tablet tools and pads.
+    pub tablet: bool,
+    pub gesture: bool,
+    /// Lid and tablet mode switches.
+    pub switch: bool,
+}
+impl Default for ForwardConfig {
+    fn default() -> Self {
+        ForwardConfig {
+            keyboard: true,
+            pointer: true,
+            touch: true,
+            tablet: true,
+            gesture: true,
+            switch: true,
+        }
+    }
+}
+impl ForwardConfig {
+    /// Whether input of `kind` is forwarded, gamepads always are while they're read at all.
+    pub(crate) fn forwards(&self, kind: InputKind) -> bool {
+        match kind {
+            InputKind::Keyboard => self.keyboard,
+            InputKind::Pointer => self.pointer,
+            InputKind::Touch => self.touch,
+            InputKind::Tablet | InputKind::TabletPad => self.tablet,
+            InputKind::Gesture => self.gesture,
+            InputKind::Switch => self.switch,
+            InputKind::Gamepad => true,
+        }
+    }
+}
+
+/// What the cursor does when it's moved past the edge of its bounds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorEdges {
+    /// It stops at the edge.
+    #[default]
+    Clamp,
+    /// It comes back in on the opposite edge, for UIs that go all the way around.
+    Wrap,
+    /// It keeps going, the bounds only decide where the middle is.
+    Infinite,
+}
+
+/// Gamepads and joysticks, which are read from evdev directly since libinput doesn't handle them.
+///
+/// The device filter applies to them the same as to everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    /// Read gamepads at all, which needs read access to their `/dev/input/event*` devices.
+    pub enabled: bool,
+    /// Fraction of an axis' range around its rest position that counts as resting.
+    pub deadzone: f32,
+    /// Scale axes to -1..1 (0..1 for triggers) instead of sending the raw values, the deadzone
+    /// only applies to normalized axes.
+    pub normalize: bool,
+}
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        GamepadConfig {
+            enabled: false,
+            deadzone: 0.1,
+            normalize: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{vec2, Vec2};
+
+    #[test]
+    fn tablet_input_outside_the_area_is_clamped_into_it() {
+        let tablet = TabletConfig {
+            area_offset: [0.25, 0.25],
+            area_size: [0.5, 0.5],
+            lock_aspect_ratio: false,
+        };
+        let surface = vec2(1000.0, 1000.0);
+        assert_eq!(
+            tablet.map(vec2(0.5, 0.5), None, surface),
+            (vec2(0.5, 0.5), false)
+        );
+        assert_eq!(
+            tablet.map(vec2(0.25, 0.75), None, surface),
+            (vec2(0.0, 1.0), false)
+        );
+        assert_eq!(
+            tablet.map(vec2(0.1, 0.5), None, surface),
+            (vec2(0.0, 0.5), true)
+        );
+        // past the tablet's own edges too
+        assert_eq!(
+            tablet.map(vec2(1.5, -0.5), None, surface),
+            (vec2(1.0, 0.0), true)
+        );
+    }
+
+    #[test]
+    fn locking_the_aspect_ratio_shrinks_the_area_around_its_center() {
+        let tablet = TabletConfig {
+            lock_aspect_ratio: true,
+            ..TabletConfig::default()
+        };
+        let tablet_size = Some(vec2(200.0, 100.0));
+        let surface = vec2(1000.0, 1000.0);
+        assert_eq!(
+            tablet.map(vec2(0.25, 0.0), tablet_size, surface),
+            (vec2(0.0, 0.0), false)
+        );
+        assert_eq!(
+            tablet.map(vec2(0.75, 1.0), tablet_size, surface),
+            (vec2(1.0, 1.0), false)
+        );
+        assert_eq!(
+            tablet.map(vec2(0.1, 0.5), tablet_size, surface),
+            (vec2(0.0, 0.5), true)
+        );
+        // without the tablet's size there's no aspect ratio to keep
+        assert_eq!(
+            tablet.map(vec2(0.1, 0.5), None, surface),
+            (vec2(0.1, 0.5), false)
+        );
+        assert_eq!(
+            tablet.map(vec2(0.1, 0.5), Some(Vec2::ZERO), surface),
+            (vec2(0.1, 0.5), false)
+        );
+    }
+
+    #[test]
+    fn an_empty_tablet_area_still_maps_to_a_position() {
+        let tablet = TabletConfig {
+            area_size: [0.0, 0.0],
+            ..TabletConfig::default()
+        };
+        let (position, clamped) = tablet.map(vec2(0.5, 0.0), None, vec2(1000.0, 1000.0));
+        assert_eq!(position, vec2(1.0, 0.0));
+        assert!(clamped);
+    }
+
+    #[test]
+    fn absolute_pointers_map_into_their_area() {
+        let surface = vec2(1920.0, 1080.0);
+        let pointer = AbsolutePointerConfig::default();
+        assert_eq!(
+            pointer.map(vec2(0.5, 0.5), surface),
+            (vec2(960.0, 540.0), vec2(0.5, 0.5))
+        );
+        let pointer = AbsolutePointerConfig {
+            area_offset: [0.5, 0.0],
+            area_size: [0.5, 1.0],
+        };
+        assert_eq!(
+            pointer.map(vec2(0.5, 0.5), surface),
+            (vec2(1440.0, 540.0), vec2(0.5, 0.5))
+        );
+    }
+
+    #[test]
+    fn absolute_pointers_past_the_edges_stay_in_their_area() {
+        let surface = vec2(1920.0, 1080.0);
+        let pointer = AbsolutePointerConfig {
+            area_offset: [0.5, 0.0],
+            area_size: [0.5, 1.0],
+        };
+        assert_eq!(
+            pointer.map(vec2(1.5, -0.5), surface),
+            (vec2(1920.0, 0.0), vec2(1.0, 0.0))
+        );
+        assert_eq!(
+            pointer.map(vec2(-1.0, 2.0), surface),
+            (vec2(960.0, 1080.0), vec2(0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn no_device_filter_accepts_everything() {
+        assert!(DeviceFilterConfig::default().accepts("Mouse", 0x046d, 0xc52b));
+    }
+
+    #[test]
+    fn devices_are_allowed_by_name_or_id() {
+        let filter = DeviceFilterConfig {
+            allow: vec!["Keyboard".to_string(), "046D:C52B".to_string()],
+            deny: Vec::new(),
+        };
+        assert!(filter.accepts("Keyboard", 0x1, 0x2));
+        assert!(filter.accepts("Mouse", 0x046d, 0xc52b));
+        assert!(!filter.accepts("Mouse", 0x046d, 0xc52c));
+        assert!(!filter.accepts("keyboard", 0x1, 0x2));
+    }
+
+    #[test]
+    fn denied_devices_are_refused_even_when_allowed() {
+        let filter = DeviceFilterConfig {
+            allow: vec!["Mouse".to_string()],
+            deny: vec!["0001:0002".to_string()],
+        };
+        assert!(!filter.accepts("Mouse", 0x1, 0x2));
+        assert!(filter.accepts("Mouse", 0x1, 0x3));
+        let deny_only = DeviceFilterConfig {
+            allow: Vec::new(),
+            deny: vec!["Mouse".to_string()],
+        };
+        assert!(!deny_only.accepts("Mouse", 0x1, 0x2));
+        assert!(deny_only.accepts("Keyboard", 0x1, 0x2));
+    }
+}
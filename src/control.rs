@@ -0,0 +1,206 @@
+//! A Unix socket taking one JSON command per line, for scripting Eclipse while it runs.
+
+use crate::cli::Args;
+use crate::config::Config;
+use nix::sys::stat::{umask, Mode};
+use nix::unistd::geteuid;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+/// A command as it's written on the socket, like `{"cmd":"focus","uid":"..."}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    /// List the connected receivers and which ones have focus.
+    Receivers,
+    /// Move the keyboard focus to the receiver with this UID.
+    Focus {
+        uid: String,
+    },
+    ToggleCapture,
+    /// Read the config file again.
+    Reload,
+    /// Press, release or tap a key as if it came from a keyboard.
+    Key {
+        key: u32,
+        pressed: Option<bool>,
+        seat: Option<String>,
+    },
+}
+
+/// A command for Eclipse to carry out, with the config for a reload already read.
+#[derive(Debug)]
+pub enum Command {
+    Receivers,
+    Focus(String),
+    ToggleCapture,
+    Reload(Box<Config>),
+    Key(InjectedKey),
+}
+
+/// A key for a libinput thread to handle like one from its keyboards.
+#[derive(Debug, Clone)]
+pub struct InjectedKey {
+    /// The evdev keycode.
+    pub key: u32,
+    /// Whether the key goes down or up, `None` presses and releases it.
+    pub pressed: Option<bool>,
+    /// The seat whose keymap and modifiers it goes through, the main one if it's `None`.
+    pub seat: Option<String>,
+}
+
+/// What a command answers with, a value to send back or what went wrong.
+pub type Reply = Result<serde_json::Value, String>;
+
+/// Sent back for every command as `{"ok":...}` or `{"error":"..."}`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(serde_json::Value),
+    Error(String),
+}
+
+pub struct ControlRequest {
+    pub command: Command,
+    pub reply: oneshot::Sender<Reply>,
+}
+
+/// Take commands on the socket at `path` forever, sending them through `tx` to be carried out.
+///
+/// A socket left behind at `path` by an Eclipse that didn't exit cleanly is replaced.
+pub async fn serve(path: PathBuf, args: Args, tx: Sender<ControlRequest>) {
+    if std::fs::metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        let _ = std::fs::remove_file(&path);
+    }
+    // anyone who can connect can type on the keyboard, so only Eclipse's user gets to. The socket
+    // is created without permissions for anyone else rather than restricted once it's there, which
+    // would leave a moment to connect in. The umask is the whole process', but Eclipse doesn't
+    // create any other files once it's started reading input
+    let previous_umask = umask(Mode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(&path);
+    umask(previous_umask);
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!(path = %path.display(), ?err, "couldn't create the control socket");
+            return;
+        }
+    };
+    info!(path = %path.display(), "listening for control commands");
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                // in case the socket's permissions were changed after all
+                match stream.peer_cred() {
+                    Ok(cred) if cred.uid() == geteuid().as_raw() => (),
+                    Ok(cred) => {
+                        warn!(
+                            uid = cred.uid(),
+                            "refused control connection from another user"
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!(
+                            ?err,
+                            "couldn't check who's connecting to the control socket"
+                        );
+                        continue;
+                    }
+                }
+                let (args, tx) = (args.clone(), tx.clone());
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(stream, &args, &tx).await {
+                        debug!(?err, "control connection failed");
+                    }
+                });
+            }
+            Err(err) => warn!(?err, "couldn't accept control connection"),
+        }
+    }
+}
+
+/// Reload the config like the reload command every time Eclipse gets SIGHUP.
+pub async fn reload_on_sighup(args: Args, tx: Sender<ControlRequest>) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            warn!(?err, "couldn't listen for SIGHUP");
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("reloading config for SIGHUP");
+        if let Err(err) = run(Request::Reload, &args, &tx).await {
+            warn!(err, "couldn't reload config");
+        }
+    }
+}
+
+/// Answer each command on `stream` in turn until it's closed.
+async fn handle_connection(
+    stream: UnixStream,
+    args: &Args,
+    tx: &Sender<ControlRequest>,
+) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(request) => run(request, args, tx).await,
+            Err(err) => Err(format!("Invalid command: {err}")),
+        };
+        let response = match reply {
+            Ok(value) => Response::Ok(value),
+            Err(err) => Response::Error(err),
+        };
+        let mut response = serde_json::to_string(&response)?;
+        response.push('\n');
+        write.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+async fn run(request: Request, args: &Args, tx: &Sender<ControlRequest>) -> Reply {
+    let command = match request {
+        Request::Receivers => Command::Receivers,
+        Request::Focus { uid } => Command::Focus(uid),
+        Request::ToggleCapture => Command::ToggleCapture,
+        Request::Reload => match args.load_config() {
+            Ok(config) => Command::Reload(Box::new(config)),
+            Err(err) => return Err(format!("{err:#}")),
+        },
+        Request::Key { key, .. } if key == 0 || key > input_event_codes::KEY_MAX!() => {
+            return Err(format!("Invalid keycode {key}"));
+        }
+        Request::Key { key, pressed, seat } => Command::Key(InjectedKey { key, pressed, seat }),
+    };
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let request = ControlRequest {
+        command,
+        reply: reply_tx,
+    };
+    let shutting_down = || "Eclipse is shutting down".to_string();
+    tx.send(request).await.map_err(|_| shutting_down())?;
+    reply_rx.await.map_err(|_| shutting_down())?
+}
+
+/// Remove the socket at `path` now that nothing's listening on it anymore.
+pub fn remove_socket(path: &Path) {
+    if let Err(err) = std::fs::remove_file(path) {
+        if err.kind() != ErrorKind::NotFound {
+            debug!(path = %path.display(), ?err, "couldn't remove the control socket");
+        }
+    }
+}
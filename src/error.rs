@@ -0,0 +1,65 @@
+//! The ways setting up Eclipse's input bridge can fail, which `run` reports through eyre.
+
+use stardust_xr_fusion::node::NodeError;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EclipseError {
+    /// Creating or using Eclipse's nodes on the server failed, usually because it went away.
+    #[error("Couldn't talk to the stardust server")]
+    Connection(#[from] NodeError),
+    #[error("Couldn't set up input")]
+    Io(#[from] std::io::Error),
+    /// An input thread panicked or stopped before it was done starting up.
+    #[error("An input thread stopped unexpectedly")]
+    InputThread,
+    #[error("Couldn't assign seat {seat:?}, does it exist?")]
+    Libinput { seat: String },
+    /// Not even the evdev defaults compile, which the configured keymaps fall back on.
+    #[error("Couldn't compile the default evdev keymap, is xkeyboard-config installed?")]
+    Keymap,
+    #[error("Unknown modifier {0:?}")]
+    UnknownModifier(String),
+    #[error("Hotkey {0:?} doesn't end with a valid key name")]
+    HotkeyKey(String),
+    #[error("Invalid hotkey {hotkey:?}")]
+    Hotkey {
+        hotkey: String,
+        #[source]
+        source: Box<EclipseError>,
+    },
+    #[error("Unknown mouse button {0:?} in button_keys")]
+    UnknownButton(String),
+    #[error("Unknown key {0:?} in inhibited_keys")]
+    UnknownKey(String),
+    /// A setting in the config file that's the right type but doesn't make sense.
+    #[error("Invalid {setting}")]
+    Setting {
+        setting: &'static str,
+        #[source]
+        source: Box<EclipseError>,
+    },
+    #[error("Couldn't read config file {}", path.display())]
+    ReadConfig {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Couldn't parse config file {}", path.display())]
+    ParseConfig {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+}
+
+impl EclipseError {
+    /// Say which setting this error is about.
+    pub fn in_setting(self, setting: &'static str) -> Self {
+        EclipseError::Setting {
+            setting,
+            source: Box::new(self),
+        }
+    }
+}
@@ -0,0 +1,773 @@
+//! The events Eclipse sends to pulse receivers.
+//!
+//! The molecules event types are flattened into these, so receivers that only know about those can
+//! still read the pulse data and just ignore the fields they don't understand.
+
+use mint::Vector2;
+use nix::time::{clock_gettime, ClockId};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use stardust_xr_fusion::core::schemas::flex::flexbuffers;
+use stardust_xr_fusion::data::{PulseReceiver, PulseSender};
+use stardust_xr_molecules::keyboard::KeyboardEvent;
+use stardust_xr_molecules::mouse::MouseEvent;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::LazyLock;
+
+pub static TOUCH_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("touch", "v1"));
+pub static TABLET_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("tablet", "v1"));
+pub static TABLET_PAD_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("tablet_pad", "v1"));
+pub static GESTURE_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("gesture", "v1"));
+pub static GAMEPAD_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("gamepad", "v1"));
+pub static SWITCH_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("switch", "v1"));
+pub static CAPTURE_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("capture", "v1"));
+pub static IDLE_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("idle", "v1"));
+pub static PAUSE_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("pause", "v1"));
+pub static TEXT_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("text_input", "v1"));
+/// The mask of Eclipse's own receiver, which takes `CursorWarp`s from receivers.
+pub static CURSOR_WARP_MASK: LazyLock<Vec<u8>> = LazyLock::new(|| mask("cursor_warp", "v1"));
+
+/// Build a pulse mask matching data with `key` set to `version`.
+fn mask(key: &str, version: &str) -> Vec<u8> {
+    let mut fbb = flexbuffers::Builder::default();
+    let mut map = fbb.start_map();
+    map.push(key, version);
+    map.end_map();
+    fbb.take_buffer()
+}
+
+/// Microseconds on the monotonic clock, which is also what libinput timestamps events with.
+pub fn timestamp() -> u64 {
+    let now = clock_gettime(ClockId::CLOCK_MONOTONIC).unwrap();
+    now.tv_sec() as u64 * 1_000_000 + now.tv_nsec() as u64 / 1000
+}
+
+/// An event read from a seat's devices, which says what seat and device it came from so receivers
+/// can tell them apart.
+pub trait SourceEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>);
+    /// Say when the input behind the event happened, for events that have a `time`.
+    fn set_time(&mut self, _time: u64) {}
+}
+impl SourceEvent for MouseInput {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+    fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+}
+impl SourceEvent for KeyboardInput {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+    fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+}
+impl SourceEvent for TouchEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+}
+impl SourceEvent for TabletEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+}
+impl SourceEvent for TabletPadEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+}
+impl SourceEvent for GestureEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+}
+impl SourceEvent for SwitchEvent {
+    fn set_source(&mut self, seat: &str, device: Option<u32>) {
+        self.seat = seat.to_string();
+        self.device = device;
+    }
+}
+
+/// A new ID for an input device, never reused while Eclipse is running.
+pub fn next_device_id() -> u32 {
+    static NEXT_DEVICE_ID: AtomicU32 = AtomicU32::new(0);
+    NEXT_DEVICE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// An event that's sent to receivers as pulse data.
+pub trait PulseEvent: Serialize {
+    /// Send this event to every receiver in `receivers`.
+    fn send_event(&self, sender: &PulseSender, receivers: &[&PulseReceiver]) {
+        let mut serializer = flexbuffers::FlexbufferSerializer::new();
+        if self.serialize(&mut serializer).is_ok() {
+            let data = serializer.take_buffer();
+            for receiver in receivers {
+                let _ = sender.send_data(receiver, &data);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseInput {
+    #[serde(flatten)]
+    pub event: MouseEvent,
+    /// Absolute pointer position in pixels on the surface absolute pointing devices are mapped to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Vector2<f32>>,
+    /// `position` from 0 to 1 across the area of the surface absolute pointers are mapped onto,
+    /// present along with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position_normalized: Option<Vector2<f32>>,
+    /// What kind of device the scroll in this event came from, only present on scroll events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scroll_source: Option<ScrollSource>,
+    /// Scroll wheel movement in 120ths of a detent, sent instead of `scroll_steps` when the wheel
+    /// units are set to v120.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scroll_v120: Option<Vector2<i32>>,
+    /// Set on the event ending a finger or continuous scroll, when the fingers lift or the scrolling
+    /// otherwise stops rather than just pausing. Only present when it's set.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub scroll_stop: bool,
+    /// `buttons_up` by name in the same order, only present if button names are turned on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub button_names_up: Option<Vec<MouseButton>>,
+    /// `buttons_down` by name in the same order, only present if button names are turned on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub button_names_down: Option<Vec<MouseButton>>,
+    /// Where the seat's cursor is after this event, in pixels from the top left of the cursor
+    /// bounds, only present if the cursor is turned on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<Vector2<f32>>,
+    /// What the buttons pressed in this event are for beyond being pressed, only present if they
+    /// stand for something that's turned on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intent: Option<ButtonIntent>,
+    /// How many times in a row the button pressed in this event has been clicked, 2 for a double
+    /// click. Only present on presses with click counting turned on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub click_count: Option<u32>,
+    /// Whether a drag with the button that was pressed first started or ended in this event, only
+    /// present with drag detection turned on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drag: Option<DragPhase>,
+    /// Whether the pointer just moved onto or off this receiver, in an event of its own with nothing
+    /// else in it. Only sent with hover events turned on, and not from any seat or device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hover: Option<HoverPhase>,
+    /// Seat the event came from, like `seat0`.
+    #[serde(default)]
+    pub seat: String,
+    /// ID of the device the event came from, shared with the `device` of every other kind of event.
+    /// Events Eclipse makes up itself, like repeated buttons and scroll momentum, don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+    /// When the event happened in microseconds on the monotonic clock like `timestamp`, from
+    /// libinput unless `timestamps` says otherwise. Orders it against keyboard events too.
+    pub time: u64,
+}
+impl PulseEvent for MouseInput {}
+impl MouseInput {
+    pub fn absolute(
+        position: impl Into<Vector2<f32>>,
+        normalized: Option<impl Into<Vector2<f32>>>,
+    ) -> Self {
+        MouseInput {
+            event: MouseEvent::new(None, None, None, None, None),
+            position: Some(position.into()),
+            position_normalized: normalized.map(Into::into),
+            scroll_source: None,
+            scroll_v120: None,
+            scroll_stop: false,
+            button_names_up: None,
+            button_names_down: None,
+            cursor: None,
+            intent: None,
+            click_count: None,
+            drag: None,
+            hover: None,
+            seat: String::new(),
+            device: None,
+            time: timestamp(),
+        }
+    }
+
+    pub fn scroll(event: MouseEvent, source: ScrollSource) -> Self {
+        MouseInput {
+            scroll_source: Some(source),
+            ..event.into()
+        }
+    }
+
+    /// Fill in the button names for the raw button codes.
+    pub fn name_buttons(&mut self) {
+        let names = |buttons: &Vec<u32>| buttons.iter().copied().map(MouseButton::from).collect();
+        self.button_names_up = self.event.buttons_up.as_ref().map(names);
+        self.button_names_down = self.event.buttons_down.as_ref().map(names);
+    }
+
+    /// Mark the event as pasting the primary selection if it presses the middle button.
+    pub fn mark_primary_paste(&mut self) {
+        let pastes = self.event.buttons_down.as_ref().is_some_and(|buttons| {
+            buttons
+                .iter()
+                .any(|&button| MouseButton::from(button) == MouseButton::Middle)
+        });
+        if pastes {
+            self.intent = Some(ButtonIntent::PastePrimary);
+        }
+    }
+
+    /// Whether there's nothing in this event for a receiver to act on.
+    pub fn is_empty(&self) -> bool {
+        self.position.is_none()
+            && self.event.delta.is_none()
+            && self.event.scroll_distance.is_none()
+            && self.event.scroll_steps.is_none()
+            && self.scroll_v120.is_none()
+            && self.event.buttons_up.is_none()
+            && self.event.buttons_down.is_none()
+    }
+}
+impl From<MouseEvent> for MouseInput {
+    fn from(event: MouseEvent) -> Self {
+        MouseInput {
+            event,
+            position: None,
+            position_normalized: None,
+            scroll_source: None,
+            scroll_v120: None,
+            scroll_stop: false,
+            button_names_up: None,
+            button_names_down: None,
+            cursor: None,
+            intent: None,
+            click_count: None,
+            drag: None,
+            hover: None,
+            seat: String::new(),
+            device: None,
+            time: timestamp(),
+        }
+    }
+}
+
+/// Mouse buttons by what they are, so receivers don't all need a table of evdev codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// The side buttons, which are usually back and forward in that order.
+    Side,
+    Extra,
+    Forward,
+    Back,
+    Task,
+    /// Anything else, only known by its raw code.
+    Other,
+}
+/// The evdev code of every button that has a name.
+const NAMED_BUTTONS: [(MouseButton, u32); 8] = [
+    (MouseButton::Left, input_event_codes::BTN_LEFT!()),
+    (MouseButton::Right, input_event_codes::BTN_RIGHT!()),
+    (MouseButton::Middle, input_event_codes::BTN_MIDDLE!()),
+    (MouseButton::Side, input_event_codes::BTN_SIDE!()),
+    (MouseButton::Extra, input_event_codes::BTN_EXTRA!()),
+    (MouseButton::Forward, input_event_codes::BTN_FORWARD!()),
+    (MouseButton::Back, input_event_codes::BTN_BACK!()),
+    (MouseButton::Task, input_event_codes::BTN_TASK!()),
+];
+impl From<u32> for MouseButton {
+    fn from(code: u32) -> Self {
+        NAMED_BUTTONS
+            .iter()
+            .find(|(_, named_code)| *named_code == code)
+            .map_or(MouseButton::Other, |(button, _)| *button)
+    }
+}
+impl MouseButton {
+    /// The evdev code of a button given by its name like in events, or by the code itself.
+    pub fn parse_code(button: &str) -> Option<u32> {
+        if let Ok(code) = button.parse() {
+            return Some(code);
+        }
+        let button: MouseButton = MouseButton::deserialize(button.into_deserializer())
+            .map_err(|_: serde::de::value::Error| ())
+            .ok()?;
+        NAMED_BUTTONS
+            .iter()
+            .find(|(named, _)| *named == button)
+            .map(|(_, code)| *code)
+    }
+}
+
+/// What pressing a button means, for receivers that would otherwise have to guess from the button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ButtonIntent {
+    /// Paste the primary selection where the pointer is, like middle click does on Linux.
+    PastePrimary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HoverPhase {
+    /// The receiver got the pointer focus.
+    Entered,
+    /// The pointer focus moved to another receiver or to none at all.
+    Left,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DragPhase {
+    /// The pointer moved further than the drag threshold with the button held, sent with the motion
+    /// that crossed it.
+    Started,
+    /// The button was released, sent with the release.
+    Ended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScrollSource {
+    /// A scroll wheel moving in detents, sent as `scroll_steps` or `scroll_v120`.
+    Wheel,
+    /// Fingers on a touchpad, sent as `scroll_distance`. Lifting them sends `scroll_stop` with a
+    /// distance of 0 so receivers know when to start kinetic scrolling.
+    Finger,
+    /// Some other continuous source like a trackball or button scrolling, sent as `scroll_distance`.
+    Continuous,
+}
+
+/// Serialized xkb modifier masks, the same form the Wayland `wl_keyboard.modifiers` event uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    pub depressed: u32,
+    pub latched: u32,
+    pub locked: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyboardInput {
+    #[serde(flatten)]
+    pub event: KeyboardEvent,
+    /// Modifier state after this event's keys were applied.
+    pub modifiers: Modifiers,
+    /// Whether this is a synthetic press generated by a key being held down.
+    pub repeat: bool,
+    /// Keysyms of the key with the modifiers from before it was pressed or released.
+    pub keysyms: Vec<u32>,
+    /// `keysyms` by their xkb names like `Return`, `a` or `F5`, in the same order.
+    #[serde(default)]
+    pub keysym_names: Vec<String>,
+    /// What a press types after going through the compose table, `None` for releases, keys that
+    /// don't type anything and keys that are part of an unfinished or cancelled compose sequence.
+    pub text: Option<String>,
+    /// Which of the configured keymaps is active, 0 for `keymap` and 1 on for `extra_keymaps`.
+    pub keymap_index: usize,
+    /// Seat the event came from, like `seat0`.
+    #[serde(default)]
+    pub seat: String,
+    /// ID of the keyboard the event came from, like `device` in mouse events. Key repeats and keys
+    /// injected through the control socket don't have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+    /// When the event happened in microseconds on the monotonic clock like `timestamp`, from
+    /// libinput unless `timestamps` says otherwise. Orders it against mouse events too.
+    pub time: u64,
+}
+impl PulseEvent for KeyboardInput {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TouchPhase {
+    Down,
+    Motion,
+    Up,
+    /// The touch was interrupted and anything it started should be undone rather than committed.
+    Cancel,
+}
+
+/// A change to a single touch point.
+#[derive(Debug, Clone, Serialize)]
+pub struct TouchEvent {
+    pub touch: String,
+    /// Identifies the finger across its whole down-motion-up sequence, unique among all touch devices on the seat.
+    pub slot: u32,
+    pub phase: TouchPhase,
+    /// Position in pixels on the surface absolute devices are mapped to, only present for `Down` and `Motion`.
+    pub position: Option<Vector2<f32>>,
+    pub seat: String,
+    /// ID of the touchscreen the touch is on, like `device` in mouse events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for TouchEvent {}
+impl TouchEvent {
+    pub fn new(slot: u32, phase: TouchPhase, position: Option<Vector2<f32>>) -> Self {
+        TouchEvent {
+            touch: "v1".to_string(),
+            slot,
+            phase,
+            position,
+            seat: String::new(),
+            device: None,
+        }
+    }
+}
+
+/// What kind of tool a tablet event is from, as far as the tablet knows what it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TabletToolType {
+    Pen,
+    /// The eraser end of a stylus, which is a tool of its own with the same serial as the tip.
+    Eraser,
+    Brush,
+    Pencil,
+    Airbrush,
+    /// A mouse bound to the tablet.
+    Mouse,
+    /// A mouse with a lens to aim through.
+    Lens,
+    /// A rotary device standing on the tablet.
+    Totem,
+}
+
+/// The state of a tablet tool (stylus) whenever it changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct TabletEvent {
+    pub tablet: String,
+    /// `None` for tools libinput doesn't know the type of.
+    pub tool_type: Option<TabletToolType>,
+    /// Serial number of the physical tool, to tell pens of the same type apart. 0 if the tablet
+    /// can't tell.
+    pub serial: u64,
+    /// Hardware ID of the tool's model, 0 if the tablet doesn't report one.
+    pub tool_id: u64,
+    /// Position in pixels on the surface absolute devices are mapped to.
+    pub position: Vector2<f32>,
+    /// How hard the tip is pressed, from 0 to 1.
+    pub pressure: f32,
+    /// Tilt away from perpendicular to the tablet surface in degrees, positive towards +x/+y.
+    pub tilt: Vector2<f32>,
+    /// Whether the tool is close enough to the tablet to be sensed.
+    pub in_proximity: bool,
+    /// Whether the tool is outside the part of the tablet that's mapped onto the surface, with
+    /// `position` clamped to the nearest edge.
+    pub out_of_area: bool,
+    pub seat: String,
+    /// ID of the tablet the tool is used on, like `device` in mouse events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for TabletEvent {}
+impl TabletEvent {
+    pub fn new(
+        position: Vector2<f32>,
+        pressure: f32,
+        tilt: Vector2<f32>,
+        in_proximity: bool,
+    ) -> Self {
+        TabletEvent {
+            tablet: "v1".to_string(),
+            tool_type: None,
+            serial: 0,
+            tool_id: 0,
+            position,
+            // a tool that left proximity can't still be pressing
+            pressure: if in_proximity {
+                pressure.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+            tilt,
+            in_proximity,
+            out_of_area: false,
+            seat: String::new(),
+            device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TabletPadInput {
+    Button {
+        number: u32,
+        pressed: bool,
+    },
+    Ring {
+        number: u32,
+        /// Angle in degrees clockwise from the top, `None` once the finger is lifted.
+        position: Option<f32>,
+        /// Degrees turned since the previous event of this touch on the ring.
+        delta: f32,
+    },
+    Strip {
+        number: u32,
+        /// From 0 at the top/left to 1 at the bottom/right, `None` once the finger is lifted.
+        position: Option<f32>,
+    },
+}
+
+/// A button, ring or strip on a tablet pad changing.
+#[derive(Debug, Clone, Serialize)]
+pub struct TabletPadEvent {
+    pub tablet_pad: String,
+    pub input: TabletPadInput,
+    /// Index of the mode group the input belongs to.
+    pub mode_group: u32,
+    /// The mode that group is currently in, so receivers can layer different shortcuts on the same input.
+    pub mode: u32,
+    pub seat: String,
+    /// ID of the tablet the pad is part of, like `device` in mouse events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for TabletPadEvent {}
+impl TabletPadEvent {
+    pub fn new(input: TabletPadInput, mode_group: u32, mode: u32) -> Self {
+        TabletPadEvent {
+            tablet_pad: "v1".to_string(),
+            input,
+            mode_group,
+            mode,
+            seat: String::new(),
+            device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GestureKind {
+    Pinch,
+    Swipe,
+    /// Fingers resting on the touchpad without moving, which has no `Update`s.
+    Hold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum GesturePhase {
+    Begin,
+    Update,
+    /// The gesture finished normally and should be committed.
+    End,
+    /// The gesture was aborted and anything it did should be undone.
+    Cancel,
+}
+
+/// A touchpad gesture, always framed by a `Begin` and either an `End` or a `Cancel`.
+#[derive(Debug, Clone, Serialize)]
+pub struct GestureEvent {
+    pub gesture: String,
+    pub kind: GestureKind,
+    pub phase: GesturePhase,
+    pub finger_count: u32,
+    /// Motion of the center of the fingers since the last update, only present on `Update`.
+    pub delta: Option<Vector2<f32>>,
+    /// Pinch only, how far the fingers spread relative to the `Begin` where it was 1.
+    pub scale: Option<f32>,
+    /// Pinch only, degrees rotated clockwise since the last update.
+    pub rotation_delta: Option<f32>,
+    pub seat: String,
+    /// ID of the touchpad the gesture is made on, like `device` in mouse events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for GestureEvent {}
+impl GestureEvent {
+    pub fn new(kind: GestureKind, phase: GesturePhase, finger_count: u32) -> Self {
+        GestureEvent {
+            gesture: "v1".to_string(),
+            kind,
+            phase,
+            finger_count,
+            delta: None,
+            scale: None,
+            rotation_delta: None,
+            seat: String::new(),
+            device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum GamepadInput {
+    /// `code` is the evdev button code, like `BTN_SOUTH`.
+    Button { code: u16, pressed: bool },
+    /// `code` is the evdev axis code, like `ABS_X`. The value is from -1 to 1 (0 to 1 for triggers)
+    /// when normalization is on, otherwise whatever the device reported.
+    Axis { code: u16, value: f32 },
+}
+
+/// A button or axis on a gamepad or joystick changing.
+#[derive(Debug, Clone, Serialize)]
+pub struct GamepadEvent {
+    pub gamepad: String,
+    /// Identifies the gamepad the input came from, never reused while Eclipse is running.
+    pub device: u32,
+    pub input: GamepadInput,
+}
+impl PulseEvent for GamepadEvent {}
+impl GamepadEvent {
+    pub fn new(device: u32, input: GamepadInput) -> Self {
+        GamepadEvent {
+            gamepad: "v1".to_string(),
+            device,
+            input,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SwitchKind {
+    /// On while the laptop lid is closed.
+    Lid,
+    /// On while a convertible is folded into a tablet, when its keyboard and touchpad are usually
+    /// out of reach.
+    TabletMode,
+}
+
+/// A switch like a laptop lid changing state.
+///
+/// Every switch is reported as off when its device is added, followed straight away by an on event
+/// if it's actually on.
+#[derive(Debug, Clone, Serialize)]
+pub struct SwitchEvent {
+    pub switch: String,
+    pub kind: SwitchKind,
+    pub on: bool,
+    pub seat: String,
+    /// ID of the device the switch is on, like `device` in mouse events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for SwitchEvent {}
+impl SwitchEvent {
+    pub fn new(kind: SwitchKind, on: bool) -> Self {
+        SwitchEvent {
+            switch: "v1".to_string(),
+            kind,
+            on,
+            seat: String::new(),
+            device: None,
+        }
+    }
+}
+
+/// Sent to Eclipse by a receiver drawing its own cursor to move the shared one, like
+/// `{"cursor_warp": "v1", "position": [960, 540], "seat": "seat0"}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CursorWarp {
+    /// Where the cursor goes, in pixels from the top left of the cursor bounds.
+    pub position: Vector2<f32>,
+    /// The seat whose cursor moves, every seat's if it's left out.
+    #[serde(default)]
+    pub seat: Option<String>,
+}
+
+/// Input was captured or released, so receivers can show that it's captured.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureEvent {
+    pub capture: String,
+    pub captured: bool,
+}
+impl PulseEvent for CaptureEvent {}
+impl CaptureEvent {
+    pub fn new(captured: bool) -> Self {
+        CaptureEvent {
+            capture: "v1".to_string(),
+            captured,
+        }
+    }
+}
+
+/// There's been no input for the configured timeout, or there is again.
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleEvent {
+    pub idle: String,
+    pub is_idle: bool,
+}
+impl PulseEvent for IdleEvent {}
+impl IdleEvent {
+    pub fn new(is_idle: bool) -> Self {
+        IdleEvent {
+            idle: "v1".to_string(),
+            is_idle,
+        }
+    }
+}
+
+/// What a key press typed, with the keymap and compose table already applied.
+///
+/// Keys that type control characters like Backspace, Enter and Tab aren't sent as text, those are
+/// only keyboard events.
+#[derive(Debug, Clone, Serialize)]
+pub struct TextEvent {
+    pub text_input: String,
+    /// UTF-8 text typed, more than one character for some compose sequences.
+    pub text: String,
+    pub seat: String,
+    /// The `device` of the keyboard event the text was typed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+impl PulseEvent for TextEvent {}
+impl TextEvent {
+    /// The text `event` typed, if it's printable.
+    pub fn from_keyboard(event: &KeyboardInput) -> Option<Self> {
+        let text = event.text.as_ref()?;
+        if text.chars().any(char::is_control) {
+            return None;
+        }
+        Some(TextEvent {
+            text_input: "v1".to_string(),
+            text: text.clone(),
+            seat: event.seat.clone(),
+            device: event.device,
+        })
+    }
+}
+
+/// Forwarding input was paused with the `toggle_pause` hotkey or resumed.
+#[derive(Debug, Clone, Serialize)]
+pub struct PauseEvent {
+    pub pause: String,
+    pub paused: bool,
+}
+impl PulseEvent for PauseEvent {}
+impl PauseEvent {
+    pub fn new(paused: bool) -> Self {
+        PauseEvent {
+            pause: "v1".to_string(),
+            paused,
+        }
+    }
+}
+
+/// Several events for the same receivers sent as a single pulse, oldest first.
+#[derive(Debug, Serialize)]
+pub struct EventBatch<'a, E> {
+    /// The keys and values of the sender's mask, the server only delivers pulses matching it.
+    #[serde(flatten)]
+    pub mask: &'a BTreeMap<String, String>,
+    pub events: &'a [E],
+}
+
+/// The keys and values of a mask made of string values like the input masks, to send along with
+/// data that has to match it.
+pub fn mask_fields(mask: &[u8]) -> BTreeMap<String, String> {
+    flexbuffers::Reader::get_root(mask)
+        .ok()
+        .and_then(|root| BTreeMap::deserialize(root).ok())
+        .unwrap_or_default()
+}
+impl<E: Serialize> PulseEvent for EventBatch<'_, E> {}
@@ -0,0 +1,340 @@
+//! Reading gamepads and joysticks straight from evdev, since libinput leaves them alone.
+
+use crate::config::{DeviceFilterConfig, GamepadConfig};
+use crate::events::{next_device_id, GamepadEvent, GamepadInput};
+use crate::idle;
+use crate::metrics::{InputKind, METRICS};
+use crate::session::SessionActivity;
+use libc::{input_absinfo, input_event, input_id};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, info, warn};
+
+/// How often `/dev/input` is checked for newly plugged in gamepads.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+nix::ioctl_read!(eviocgid, b'E', 0x02, input_id);
+nix::ioctl_read_buf!(eviocgname, b'E', 0x06, u8);
+nix::ioctl_read_buf!(eviocgbit_key, b'E', 0x20 + input_event_codes::EV_KEY!(), u8);
+nix::ioctl_read_buf!(eviocgbit_abs, b'E', 0x20 + input_event_codes::EV_ABS!(), u8);
+
+/// `EVIOCGABS`, which has the axis in its request code so nix can't generate it.
+fn eviocgabs(fd: RawFd, axis: u16) -> nix::Result<input_absinfo> {
+    let request = nix::request_code_read!(b'E', 0x40 + axis, std::mem::size_of::<input_absinfo>());
+    // SAFETY: the kernel writes a single input_absinfo, which is plain integers so zeroed is valid
+    let mut info: input_absinfo = unsafe { std::mem::zeroed() };
+    Errno::result(unsafe { libc::ioctl(fd, request, &mut info) })?;
+    Ok(info)
+}
+
+/// Whether `bit` is set in an evdev bitmask.
+fn has_bit(bits: &[u8], bit: u16) -> bool {
+    bits.get(bit as usize / 8)
+        .is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+}
+
+/// Map a raw axis value onto -1..1, or 0..1 for axes like triggers that don't go negative, with
+/// anything within `deadzone` of the rest position snapped to it.
+fn normalize(raw: i32, info: &input_absinfo, deadzone: f32) -> f32 {
+    if info.maximum <= info.minimum {
+        return 0.0;
+    }
+    let unit = (raw - info.minimum) as f32 / (info.maximum - info.minimum) as f32;
+    let value = if info.minimum < 0 {
+        unit * 2.0 - 1.0
+    } else {
+        unit
+    };
+    // rescale what's outside the deadzone so the axis still covers its whole range
+    let deadzone = deadzone.clamp(0.0, 0.99);
+    let magnitude = ((value.abs() - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+    magnitude.copysign(value)
+}
+
+struct Gamepad {
+    file: File,
+    path: PathBuf,
+    /// Identifies the gamepad in events, never reused while Eclipse is running.
+    number: u32,
+    name: String,
+    id: input_id,
+    /// Range of every absolute axis the gamepad has, by axis code.
+    axes: HashMap<u16, input_absinfo>,
+    /// Last value sent for every axis, so values snapped to the deadzone aren't sent over and over.
+    axis_values: HashMap<u16, f32>,
+    held_buttons: HashSet<u16>,
+}
+impl Gamepad {
+    /// Open the evdev device at `path` if it's a gamepad or joystick.
+    fn open(path: &Path, number: u32) -> std::io::Result<Option<Self>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+
+        let mut keys = [0u8; input_event_codes::KEY_MAX!() / 8 + 1];
+        unsafe { eviocgbit_key(fd, &mut keys) }?;
+        if !has_bit(&keys, input_event_codes::BTN_GAMEPAD!())
+            && !has_bit(&keys, input_event_codes::BTN_JOYSTICK!())
+        {
+            return Ok(None);
+        }
+
+        let mut name = [0u8; 256];
+        unsafe { eviocgname(fd, &mut name) }?;
+        let name = CStr::from_bytes_until_nul(&name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // SAFETY: input_id is plain integers so zeroed is valid
+        let mut id: input_id = unsafe { std::mem::zeroed() };
+        unsafe { eviocgid(fd, &mut id) }?;
+
+        let mut abs = [0u8; input_event_codes::ABS_MAX!() / 8 + 1];
+        unsafe { eviocgbit_abs(fd, &mut abs) }?;
+        let axes = (0..=input_event_codes::ABS_MAX!())
+            .filter(|axis| has_bit(&abs, *axis))
+            .map(|axis| Ok((axis, eviocgabs(fd, axis)?)))
+            .collect::<nix::Result<_>>()?;
+
+        Ok(Some(Gamepad {
+            file,
+            path: path.to_path_buf(),
+            number,
+            name,
+            id,
+            axes,
+            axis_values: HashMap::new(),
+            held_buttons: HashSet::new(),
+        }))
+    }
+
+    /// Read everything the gamepad has queued up, failing once it's been unplugged.
+    fn read(
+        &mut self,
+        config: &GamepadConfig,
+        events: &mut Vec<GamepadEvent>,
+    ) -> std::io::Result<()> {
+        let mut buffer = [0u8; std::mem::size_of::<input_event>() * 64];
+        loop {
+            let len = match self.file.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            };
+            for chunk in buffer[..len].chunks_exact(std::mem::size_of::<input_event>()) {
+                // SAFETY: evdev only ever returns whole input_events
+                let event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast::<input_event>()) };
+                if let Some(input) = self.input(config, &event) {
+                    events.push(GamepadEvent::new(self.number, input));
+                }
+            }
+        }
+    }
+
+    fn input(&mut self, config: &GamepadConfig, event: &input_event) -> Option<GamepadInput> {
+        match event.type_ {
+            // 2 is autorepeat, which doesn't mean anything for gamepad buttons
+            input_event_codes::EV_KEY!() if event.value != 2 => {
+                let pressed = event.value == 1;
+                if pressed {
+                    self.held_buttons.insert(event.code);
+                } else {
+                    self.held_buttons.remove(&event.code);
+                }
+                Some(GamepadInput::Button {
+                    code: event.code,
+                    pressed,
+                })
+            }
+            input_event_codes::EV_ABS!() => {
+                let value = match self.axes.get(&event.code) {
+                    Some(info) if config.normalize => normalize(event.value, info, config.deadzone),
+                    _ => event.value as f32,
+                };
+                if self.axis_values.insert(event.code, value) == Some(value) {
+                    return None;
+                }
+                Some(GamepadInput::Axis {
+                    code: event.code,
+                    value,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Release every button still held, returning the events to send for them.
+    fn release(&mut self) -> Vec<GamepadEvent> {
+        self.held_buttons
+            .drain()
+            .map(|code| {
+                GamepadEvent::new(
+                    self.number,
+                    GamepadInput::Button {
+                        code,
+                        pressed: false,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Every gamepad in `/dev/input` that's in use.
+struct Gamepads {
+    config: GamepadConfig,
+    devices: DeviceFilterConfig,
+    gamepads: Vec<Gamepad>,
+    /// Devices that aren't gamepads or aren't allowed, so they aren't opened again on every scan.
+    ignored: HashSet<PathBuf>,
+}
+impl Gamepads {
+    /// Open any gamepads that were plugged in since the last scan.
+    fn scan(&mut self) {
+        let Ok(entries) = std::fs::read_dir("/dev/input") else {
+            return;
+        };
+        let paths = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("event"))
+            })
+            .collect::<HashSet<_>>();
+        // the kernel reuses event numbers, what shows up there next could be a gamepad
+        self.ignored.retain(|path| paths.contains(path));
+
+        for path in paths {
+            if self.ignored.contains(&path)
+                || self.gamepads.iter().any(|gamepad| gamepad.path == path)
+            {
+                continue;
+            }
+            match Gamepad::open(&path, next_device_id()) {
+                Ok(Some(gamepad))
+                    if self.devices.accepts(
+                        &gamepad.name,
+                        gamepad.id.vendor.into(),
+                        gamepad.id.product.into(),
+                    ) =>
+                {
+                    info!(
+                        gamepad = gamepad.name,
+                        number = gamepad.number,
+                        "using gamepad"
+                    );
+                    self.gamepads.push(gamepad);
+                }
+                Ok(Some(gamepad)) => {
+                    info!(gamepad = gamepad.name, "ignoring gamepad");
+                    self.ignored.insert(path);
+                }
+                Ok(None) => {
+                    self.ignored.insert(path);
+                }
+                Err(err) => {
+                    debug!(path = %path.display(), ?err, "couldn't open input device");
+                    self.ignored.insert(path);
+                }
+            }
+        }
+    }
+
+    /// Read every gamepad, dropping the ones that were unplugged.
+    fn read(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        self.gamepads
+            .retain_mut(|gamepad| match gamepad.read(&self.config, &mut events) {
+                Ok(()) => true,
+                Err(err) => {
+                    info!(gamepad = gamepad.name, ?err, "gamepad removed");
+                    // nothing will ever release what's still held on it otherwise
+                    events.extend(gamepad.release());
+                    false
+                }
+            });
+        events
+    }
+}
+
+/// Read every allowed gamepad and joystick and send their input through `tx`, picking up ones
+/// plugged in later, until the other end of `stop` is closed.
+pub fn read_gamepads(
+    config: GamepadConfig,
+    devices: DeviceFilterConfig,
+    tx: Sender<GamepadEvent>,
+    session: SessionActivity,
+    stop: UnixStream,
+) {
+    let mut gamepads = Gamepads {
+        config,
+        devices,
+        gamepads: Vec::new(),
+        ignored: HashSet::new(),
+    };
+    let send = |events: Vec<GamepadEvent>| {
+        // another session is using the seat
+        if !session.is_active() {
+            return;
+        }
+        for event in events {
+            METRICS.received(InputKind::Gamepad);
+            idle::input_happened();
+            let _ = tx.blocking_send(event);
+        }
+    };
+    let stop_pollfd = PollFd::new(stop.as_raw_fd(), PollFlags::POLLIN);
+    let mut next_scan = Instant::now();
+    loop {
+        if Instant::now() >= next_scan {
+            gamepads.scan();
+            next_scan = Instant::now() + RESCAN_INTERVAL;
+        }
+        let mut pollfds = gamepads
+            .gamepads
+            .iter()
+            .map(|gamepad| PollFd::new(gamepad.file.as_raw_fd(), PollFlags::POLLIN))
+            .chain([stop_pollfd])
+            .collect::<Vec<_>>();
+        let timeout = next_scan.saturating_duration_since(Instant::now());
+        match poll(&mut pollfds, crate::poll_timeout(Some(timeout))) {
+            Ok(_) => (),
+            Err(Errno::EINTR) => continue,
+            Err(err) => {
+                warn!(?err, "couldn't poll the gamepads, stopping reading them");
+                break;
+            }
+        }
+        if pollfds
+            .last()
+            .and_then(|pollfd| pollfd.revents())
+            .is_some_and(|revents| !revents.is_empty())
+        {
+            break;
+        }
+        send(gamepads.read());
+    }
+
+    // don't leave anything stuck down in whatever had focus
+    send(
+        gamepads
+            .gamepads
+            .iter_mut()
+            .flat_map(Gamepad::release)
+            .collect(),
+    );
+}
@@ -0,0 +1,87 @@
+//! A periodic log line showing the libinput threads and the event loop are still alive, so a wedged
+//! input bridge gets noticed.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+static HEARTBEAT: LazyLock<Heartbeat> = LazyLock::new(Heartbeat::default);
+
+#[derive(Debug, Default)]
+struct Heartbeat {
+    /// When each seat's libinput thread last went around its loop, `None` once it's stopped.
+    libinput: Mutex<BTreeMap<String, Option<Instant>>>,
+    /// When the event loop last handled a frame.
+    last_frame: Mutex<Option<Instant>>,
+    mouse_receivers: AtomicUsize,
+    keyboard_receivers: AtomicUsize,
+}
+
+/// Note that the libinput thread for `seat` is still going.
+pub fn libinput_alive(seat: &str) {
+    let now = Instant::now();
+    let mut libinput = HEARTBEAT.libinput.lock().unwrap();
+    match libinput.get_mut(seat) {
+        Some(last) => *last = Some(now),
+        None => {
+            libinput.insert(seat.to_string(), Some(now));
+        }
+    }
+}
+
+/// Note that the libinput thread for `seat` has stopped.
+pub fn libinput_stopped(seat: &str) {
+    HEARTBEAT
+        .libinput
+        .lock()
+        .unwrap()
+        .insert(seat.to_string(), None);
+}
+
+/// Note that the event loop handled a frame with this many receivers connected.
+pub fn frame(mouse_receivers: usize, keyboard_receivers: usize) {
+    *HEARTBEAT.last_frame.lock().unwrap() = Some(Instant::now());
+    HEARTBEAT
+        .mouse_receivers
+        .store(mouse_receivers, Ordering::Relaxed);
+    HEARTBEAT
+        .keyboard_receivers
+        .store(keyboard_receivers, Ordering::Relaxed);
+}
+
+/// Log the heartbeat every `interval` forever. The libinput threads wake up at least that often,
+/// so one that's been quiet for longer is stuck.
+pub async fn log_periodically(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let now = Instant::now();
+        let libinput = HEARTBEAT.libinput.lock().unwrap().clone();
+        for (seat, last) in &libinput {
+            match last {
+                None => warn!(seat, "libinput thread isn't running"),
+                Some(last) if now.duration_since(*last) > interval * 2 => warn!(
+                    seat,
+                    secs_since_alive = now.duration_since(*last).as_secs(),
+                    "libinput thread seems stuck"
+                ),
+                Some(_) => (),
+            }
+        }
+        let secs_since_frame = HEARTBEAT
+            .last_frame
+            .lock()
+            .unwrap()
+            .map(|last| now.duration_since(last).as_secs());
+        info!(
+            seats = libinput.values().filter(|last| last.is_some()).count(),
+            secs_since_frame,
+            mouse_receivers = HEARTBEAT.mouse_receivers.load(Ordering::Relaxed),
+            keyboard_receivers = HEARTBEAT.keyboard_receivers.load(Ordering::Relaxed),
+            "heartbeat"
+        );
+    }
+}
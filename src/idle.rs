@@ -0,0 +1,46 @@
+//! Noticing when no input has happened for a while.
+
+use crate::events::timestamp;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// `timestamp` of the latest input from any device.
+static LAST_INPUT: AtomicU64 = AtomicU64::new(0);
+
+/// Note that input just happened, from whichever thread read it.
+pub fn input_happened() {
+    LAST_INPUT.store(timestamp(), Ordering::Relaxed);
+}
+
+/// Works out when input goes idle and when it resumes.
+pub struct IdleTracker {
+    timeout: Duration,
+    /// When tracking started, which counts as input so it isn't idle straight away.
+    start: u64,
+    idle: bool,
+}
+impl IdleTracker {
+    pub fn new(timeout: Duration) -> Self {
+        IdleTracker {
+            timeout,
+            start: timestamp(),
+            idle: false,
+        }
+    }
+
+    /// Change how long input has to stop for, keeping whether it's idle now.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Whether input just became idle (`true`) or resumed (`false`), `None` if neither happened.
+    pub fn update(&mut self) -> Option<bool> {
+        let last_input = LAST_INPUT.load(Ordering::Relaxed).max(self.start);
+        let since_input = Duration::from_micros(timestamp().saturating_sub(last_input));
+        let idle = since_input >= self.timeout;
+        (idle != self.idle).then(|| {
+            self.idle = idle;
+            idle
+        })
+    }
+}
@@ -0,0 +1,702 @@
+//! Translating libinput keys with xkb.
+
+use crate::config::{HotkeyConfig, KeyRepeatConfig, KeymapConfig};
+use crate::error::EclipseError;
+use crate::events::{timestamp, KeyboardInput, Modifiers};
+use input::{Device, Led};
+use stardust_xr_molecules::keyboard::KeyboardEvent;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use xkbcommon::xkb::compose::{self, FeedResult, Status};
+use xkbcommon::xkb::{
+    keysym_from_name, keysym_get_name, Context, ContextFlags, KEY_NoSymbol, KeyDirection, Keycode,
+    Keymap, Keysym, State, CONTEXT_NO_ENVIRONMENT_NAMES, CONTEXT_NO_FLAGS, KEYMAP_COMPILE_NO_FLAGS,
+    KEYSYM_CASE_INSENSITIVE, LED_NAME_CAPS, LED_NAME_NUM, LED_NAME_SCROLL, MOD_NAME_ALT,
+    MOD_NAME_CTRL, MOD_NAME_LOGO, MOD_NAME_SHIFT, STATE_LEDS, STATE_MODS_DEPRESSED,
+    STATE_MODS_EFFECTIVE, STATE_MODS_LATCHED, STATE_MODS_LOCKED,
+};
+
+/// The modifiers config options can use, by the name they're written with and their xkb name.
+const HOTKEY_MODIFIERS: [(&str, &str); 4] = [
+    ("Shift", MOD_NAME_SHIFT),
+    ("Ctrl", MOD_NAME_CTRL),
+    ("Alt", MOD_NAME_ALT),
+    ("Super", MOD_NAME_LOGO),
+];
+
+/// Look up the xkb name of a modifier written like in a hotkey.
+pub fn modifier(name: &str) -> Result<&'static str, EclipseError> {
+    HOTKEY_MODIFIERS
+        .iter()
+        .find(|(modifier, _)| modifier.eq_ignore_ascii_case(name))
+        .map(|(_, xkb_name)| *xkb_name)
+        .ok_or_else(|| EclipseError::UnknownModifier(name.to_string()))
+}
+
+/// The key that's pressed to type each modifier, by its xkb name.
+const MODIFIER_KEYS: [(&str, u32); 4] = [
+    (MOD_NAME_SHIFT, input_event_codes::KEY_LEFTSHIFT!()),
+    (MOD_NAME_CTRL, input_event_codes::KEY_LEFTCTRL!()),
+    (MOD_NAME_ALT, input_event_codes::KEY_LEFTALT!()),
+    (MOD_NAME_LOGO, input_event_codes::KEY_LEFTMETA!()),
+];
+
+/// Something a hotkey does inside Eclipse instead of being sent to a receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    FocusNext,
+    ToggleCapture,
+    /// Handled by `KeyboardState` itself, so it's never sent on.
+    NextKeymap,
+    TogglePause,
+    /// Handled by the libinput thread, so it works whether or not Eclipse is connected.
+    Quit,
+}
+
+/// A key combination like `Super+Tab`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hotkey {
+    /// xkb names of the modifiers that have to be held, any others must not be.
+    modifiers: Vec<&'static str>,
+    /// The key's keysym without any modifiers applied.
+    keysym: Keysym,
+}
+impl Hotkey {
+    /// Parse modifier names followed by a keysym name, all separated by `+`.
+    pub fn parse(hotkey: &str) -> Result<Self, EclipseError> {
+        let mut parts = hotkey.split('+').map(str::trim).collect::<Vec<_>>();
+        let keysym = parts
+            .pop()
+            .filter(|key| !key.is_empty())
+            .map(|key| keysym_from_name(key, KEYSYM_CASE_INSENSITIVE))
+            .filter(|keysym| *keysym != KEY_NoSymbol)
+            .ok_or_else(|| EclipseError::HotkeyKey(hotkey.to_string()))?;
+        let modifiers = parts
+            .into_iter()
+            .map(|name| {
+                modifier(name).map_err(|err| EclipseError::Hotkey {
+                    hotkey: hotkey.to_string(),
+                    source: Box::new(err),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Hotkey { modifiers, keysym })
+    }
+}
+
+/// Parse every hotkey that's set in `config`.
+pub fn load_hotkeys(config: &HotkeyConfig) -> Result<Vec<(Hotkey, Action)>, EclipseError> {
+    [
+        (&config.focus_next, Action::FocusNext),
+        (&config.toggle_capture, Action::ToggleCapture),
+        (&config.next_keymap, Action::NextKeymap),
+        (&config.toggle_pause, Action::TogglePause),
+        (&config.quit, Action::Quit),
+    ]
+    .into_iter()
+    .filter(|(hotkey, _)| !hotkey.is_empty())
+    .map(|(hotkey, action)| Ok((Hotkey::parse(hotkey)?, action)))
+    .collect()
+}
+
+/// A key that's never sent to receivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InhibitedKey {
+    /// An evdev keycode, whatever the keymap makes of it.
+    Code(u32),
+    /// Every key with this keysym without any modifiers applied on the active keymap.
+    Keysym(Keysym),
+}
+impl InhibitedKey {
+    /// Parse an evdev keycode or a keysym name.
+    pub fn parse(key: &str) -> Result<Self, EclipseError> {
+        let key = key.trim();
+        if let Ok(code) = key.parse() {
+            return Ok(InhibitedKey::Code(code));
+        }
+        Some(keysym_from_name(key, KEYSYM_CASE_INSENSITIVE))
+            .filter(|keysym| !key.is_empty() && *keysym != KEY_NoSymbol)
+            .map(InhibitedKey::Keysym)
+            .ok_or_else(|| EclipseError::UnknownKey(key.to_string()))
+    }
+}
+
+/// What a key press or release turned into.
+pub struct KeyResult {
+    /// The events to send to the focused receiver, none if a hotkey used the key or it didn't change.
+    pub inputs: Vec<KeyboardInput>,
+    pub action: Option<Action>,
+    /// The new LED state if the key changed it.
+    pub leds: Option<Led>,
+}
+
+/// Convert an evdev keycode from libinput to the keycode xkb uses for the same key, which is 8
+/// higher as it was in X11. Every keycode handed to xkb has to go through this, otherwise KEY_A (30)
+/// is taken as the xkb keycode of KEY_U (22) and comes out as `u` instead of `a`.
+fn xkb_keycode(evdev_keycode: u32) -> Keycode {
+    evdev_keycode + 8
+}
+
+/// The inverse of `xkb_keycode`.
+fn evdev_keycode(xkb_keycode: Keycode) -> u32 {
+    xkb_keycode - 8
+}
+
+fn keysym_names(keysyms: &[Keysym]) -> Vec<String> {
+    keysyms.iter().copied().map(keysym_get_name).collect()
+}
+
+/// Keeps xkb's view of the keyboard in sync with the keys libinput reports.
+pub struct KeyboardState {
+    /// Every keymap that can be switched to, in order.
+    keymaps: Vec<Keymap>,
+    active_keymap: usize,
+    keymap: Keymap,
+    state: State,
+    repeat_config: KeyRepeatConfig,
+    /// The key that's being held down and when it should repeat next.
+    repeating: Option<(u32, Instant)>,
+    /// Keys held down on each keyboard, so they can be released if it's unplugged. Keys injected
+    /// through the control socket are held under `None`.
+    held: HashMap<Option<Device>, HashSet<u32>>,
+    hotkeys: Vec<(Hotkey, Action)>,
+    /// Keys whose press triggered a hotkey, so their release isn't sent either.
+    swallowed: HashSet<u32>,
+    inhibited: Vec<InhibitedKey>,
+    /// Inhibited keys held down on each keyboard, which xkb knows about but receivers don't.
+    inhibited_held: HashMap<Option<Device>, HashSet<u32>>,
+    /// Compose sequence in progress, `None` if there's no compose table for the locale.
+    compose: Option<compose::State>,
+}
+impl KeyboardState {
+    /// Start out with the first of `keymaps`, which can't be empty.
+    pub fn new(
+        keymaps: Vec<Keymap>,
+        repeat_config: KeyRepeatConfig,
+        hotkeys: Vec<(Hotkey, Action)>,
+        inhibited: Vec<InhibitedKey>,
+    ) -> Self {
+        let keymap = keymaps[0].clone();
+        let state = State::new(&keymap);
+        KeyboardState {
+            keymaps,
+            active_keymap: 0,
+            keymap,
+            state,
+            repeat_config,
+            repeating: None,
+            held: HashMap::new(),
+            hotkeys,
+            swallowed: HashSet::new(),
+            inhibited,
+            inhibited_held: HashMap::new(),
+            compose: load_compose(),
+        }
+    }
+
+    /// Apply a key press or release from `device`, `None` for an injected one, and work out what
+    /// it does.
+    pub fn key(&mut self, device: Option<&Device>, key: u32, pressed: bool) -> KeyResult {
+        if pressed {
+            if let Some(action) = self.hotkey(key) {
+                self.swallowed.insert(key);
+                if action == Action::NextKeymap {
+                    return KeyResult {
+                        inputs: self.next_keymap(),
+                        action: None,
+                        leds: Some(self.leds()),
+                    };
+                }
+                return KeyResult {
+                    inputs: Vec::new(),
+                    action: Some(action),
+                    leds: None,
+                };
+            }
+        } else if self.swallowed.remove(&key) {
+            return KeyResult {
+                inputs: Vec::new(),
+                action: None,
+                leds: None,
+            };
+        }
+        let inhibited_held = self
+            .inhibited_held
+            .get(&device.cloned())
+            .is_some_and(|held| held.contains(&key));
+        // the release goes by the press so it's swallowed even if the keymap changed in between
+        if inhibited_held || (pressed && self.inhibited(key)) {
+            return self.inhibited_key(device, key, pressed);
+        }
+
+        let held = self.held.entry(device.cloned()).or_default();
+        // some keyboards repeat a press without a release in between, only pass on real changes
+        let changed = if pressed {
+            held.insert(key)
+        } else {
+            held.remove(&key)
+        };
+        if !changed {
+            return KeyResult {
+                inputs: Vec::new(),
+                action: None,
+                leds: None,
+            };
+        }
+        let (input, leds) = self.update(key, pressed);
+        KeyResult {
+            inputs: vec![input],
+            action: None,
+            leds,
+        }
+    }
+
+    fn inhibited(&self, key: u32) -> bool {
+        let keycode = xkb_keycode(key);
+        let layout = self.state.key_get_layout(keycode);
+        let keysyms = self.keymap.key_get_syms_by_level(keycode, layout, 0);
+        self.inhibited.iter().any(|inhibited| match inhibited {
+            InhibitedKey::Code(code) => *code == key,
+            InhibitedKey::Keysym(keysym) => keysyms.contains(keysym),
+        })
+    }
+
+    /// Keep xkb up to date with an inhibited key so it still works as a hotkey modifier, without
+    /// sending it anywhere or letting it repeat.
+    fn inhibited_key(&mut self, device: Option<&Device>, key: u32, pressed: bool) -> KeyResult {
+        let held = self.inhibited_held.entry(device.cloned()).or_default();
+        let changed = if pressed {
+            held.insert(key)
+        } else {
+            held.remove(&key)
+        };
+        // it doesn't go through compose either, receivers would see a sequence made of keys they
+        // never got
+        let leds = changed.then(|| self.update_xkb(key, pressed)).flatten();
+        KeyResult {
+            inputs: Vec::new(),
+            action: None,
+            leds,
+        }
+    }
+
+    /// Switch to the next keymap with a fresh state, so no modifiers or locks carry over.
+    ///
+    /// Every held key is released first, the modifiers among them included, since the new keymap
+    /// could give them a different meaning. They stay released until they're pressed again.
+    fn next_keymap(&mut self) -> Vec<KeyboardInput> {
+        let mut inputs = self.release_all();
+        self.active_keymap = (self.active_keymap + 1) % self.keymaps.len();
+        self.keymap = self.keymaps[self.active_keymap].clone();
+        self.state = State::new(&self.keymap);
+        self.repeating = None;
+        if let Some(compose) = &mut self.compose {
+            compose.reset();
+        }
+        info!(keymap_index = self.active_keymap, "switched keymap");
+        inputs.push(self.keymap_event());
+        inputs
+    }
+
+    /// Start over with new settings, releasing everything held first so no key is released with
+    /// a different keymap than it was pressed with.
+    pub fn reload(
+        &mut self,
+        keymaps: Vec<Keymap>,
+        repeat_config: KeyRepeatConfig,
+        hotkeys: Vec<(Hotkey, Action)>,
+        inhibited: Vec<InhibitedKey>,
+    ) -> Vec<KeyboardInput> {
+        let mut inputs = self.release_all();
+        *self = KeyboardState::new(keymaps, repeat_config, hotkeys, inhibited);
+        inputs.push(self.keymap_event());
+        inputs
+    }
+
+    /// An event with nothing but the keymap, which lets receivers pick up a new keymap even if
+    /// nothing was held.
+    fn keymap_event(&self) -> KeyboardInput {
+        KeyboardInput {
+            event: KeyboardEvent::new(Some(&self.keymap), None, None),
+            modifiers: self.modifiers(),
+            repeat: false,
+            keysyms: Vec::new(),
+            keysym_names: Vec::new(),
+            text: None,
+            keymap_index: self.active_keymap,
+            seat: String::new(),
+            device: None,
+            time: timestamp(),
+        }
+    }
+
+    /// The evdev keys to press in turn to type the key combination `keys` on the active keymap,
+    /// modifiers first. `None` if no key on the keymap has the keysym.
+    pub fn keys_for(&self, keys: &Hotkey) -> Option<Vec<u32>> {
+        let key = (self.keymap.min_keycode()..=self.keymap.max_keycode()).find(|&keycode| {
+            let layout = self.state.key_get_layout(keycode);
+            self.keymap
+                .key_get_syms_by_level(keycode, layout, 0)
+                .contains(&keys.keysym)
+        })?;
+        let modifiers = keys.modifiers.iter().filter_map(|modifier| {
+            MODIFIER_KEYS
+                .iter()
+                .find(|(name, _)| name == modifier)
+                .map(|(_, key)| *key)
+        });
+        Some(modifiers.chain([evdev_keycode(key)]).collect())
+    }
+
+    /// Whether `key` is on the active keymap at all.
+    pub fn has_key(&self, key: u32) -> bool {
+        self.keymap.key_get_name(xkb_keycode(key)).is_some()
+    }
+
+    /// The action of the hotkey pressing `key` completes, if there is one.
+    fn hotkey(&self, key: u32) -> Option<Action> {
+        let keycode = xkb_keycode(key);
+        let layout = self.state.key_get_layout(keycode);
+        let keysyms = self.keymap.key_get_syms_by_level(keycode, layout, 0);
+        self.hotkeys
+            .iter()
+            .find(|(hotkey, _)| {
+                keysyms.contains(&hotkey.keysym)
+                    && HOTKEY_MODIFIERS.iter().all(|(_, modifier)| {
+                        self.state
+                            .mod_name_is_active(modifier, STATE_MODS_EFFECTIVE)
+                            == hotkey.modifiers.contains(modifier)
+                    })
+            })
+            .map(|(_, action)| *action)
+    }
+
+    /// Release every key still held on `device`, returning the events to send for them.
+    pub fn release_device(&mut self, device: &Device) -> Vec<KeyboardInput> {
+        let device = Some(device.clone());
+        for key in self.inhibited_held.remove(&device).unwrap_or_default() {
+            self.update_xkb(key, false);
+        }
+        let Some(held) = self.held.remove(&device) else {
+            return Vec::new();
+        };
+        held.into_iter()
+            .map(|key| self.update(key, false).0)
+            .collect()
+    }
+
+    /// Release every key still held on any keyboard, returning the events to send for them.
+    pub fn release_all(&mut self) -> Vec<KeyboardInput> {
+        for key in std::mem::take(&mut self.inhibited_held)
+            .into_values()
+            .flatten()
+        {
+            self.update_xkb(key, false);
+        }
+        std::mem::take(&mut self.held)
+            .into_values()
+            .flatten()
+            .map(|key| self.update(key, false).0)
+            .collect()
+    }
+
+    fn update(&mut self, key: u32, pressed: bool) -> (KeyboardInput, Option<Led>) {
+        let keysyms = self.state.key_get_syms(xkb_keycode(key)).to_vec();
+        let text = if pressed { self.text(key) } else { None };
+        let leds = self.update_xkb(key, pressed);
+
+        if pressed {
+            // like other keyboards only the last key pressed repeats
+            self.repeating = (self.repeat_config.rate > 0.0
+                && self.keymap.key_repeats(xkb_keycode(key)))
+            .then(|| {
+                (
+                    key,
+                    Instant::now() + Duration::from_millis(self.repeat_config.delay),
+                )
+            });
+        } else if self
+            .repeating
+            .is_some_and(|(repeating, _)| repeating == key)
+        {
+            self.repeating = None;
+        }
+
+        let event = KeyboardInput {
+            event: KeyboardEvent::new(
+                Some(&self.keymap),
+                (!pressed).then(|| vec![key]),
+                pressed.then(|| vec![key]),
+            ),
+            modifiers: self.modifiers(),
+            repeat: false,
+            keysym_names: keysym_names(&keysyms),
+            keysyms,
+            text,
+            keymap_index: self.active_keymap,
+            seat: String::new(),
+            device: None,
+            time: timestamp(),
+        };
+        (event, leds)
+    }
+
+    /// Press or release `key` in the xkb state alone, returning the LEDs if they changed.
+    fn update_xkb(&mut self, key: u32, pressed: bool) -> Option<Led> {
+        let direction = if pressed {
+            KeyDirection::Down
+        } else {
+            KeyDirection::Up
+        };
+        let changed = self.state.update_key(xkb_keycode(key), direction);
+        (changed & STATE_LEDS != 0).then(|| self.leds())
+    }
+
+    /// What pressing `key` types, run through the compose table so dead keys and compose sequences
+    /// turn into the character they stand for.
+    fn text(&mut self, key: u32) -> Option<String> {
+        let keycode = xkb_keycode(key);
+        if let Some(compose) = &mut self.compose {
+            // keys that can't be part of a sequence, like modifiers, are ignored and type as usual
+            if compose.feed(self.state.key_get_one_sym(keycode)) == FeedResult::Accepted {
+                match compose.status() {
+                    Status::Composing => return None,
+                    Status::Composed => {
+                        let text = compose.utf8();
+                        compose.reset();
+                        return text;
+                    }
+                    Status::Cancelled => {
+                        compose.reset();
+                        return None;
+                    }
+                    Status::Nothing => (),
+                }
+            }
+        }
+        Some(self.state.key_get_utf8(keycode)).filter(|text| !text.is_empty())
+    }
+
+    /// The lock LEDs that should be lit on the physical keyboard.
+    pub fn leds(&self) -> Led {
+        [
+            (LED_NAME_NUM, Led::NUMLOCK),
+            (LED_NAME_CAPS, Led::CAPSLOCK),
+            (LED_NAME_SCROLL, Led::SCROLLLOCK),
+        ]
+        .into_iter()
+        .filter(|(name, _)| self.state.led_name_is_active(name))
+        .fold(Led::empty(), |leds, (_, led)| leds | led)
+    }
+
+    /// When the held key will next repeat, if one is being held.
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.repeating.map(|(_, next)| next)
+    }
+
+    /// Generate the repeated press for the held key if it's due.
+    pub fn repeat(&mut self, now: Instant) -> Option<KeyboardInput> {
+        let (key, next) = self.repeating.as_mut()?;
+        if *next > now {
+            return None;
+        }
+        // don't try to catch up on missed repeats and then send them all in a burst
+        *next = now + Duration::from_secs_f32(1.0 / self.repeat_config.rate);
+        let keycode = xkb_keycode(*key);
+        let keysyms = self.state.key_get_syms(keycode).to_vec();
+        Some(KeyboardInput {
+            event: KeyboardEvent::new(Some(&self.keymap), None, Some(vec![*key])),
+            modifiers: self.modifiers(),
+            repeat: true,
+            keysym_names: keysym_names(&keysyms),
+            keysyms,
+            // repeats don't go through compose, a sequence can't consist of the same key held down
+            text: Some(self.state.key_get_utf8(keycode)).filter(|text| !text.is_empty()),
+            keymap_index: self.active_keymap,
+            seat: String::new(),
+            device: None,
+            time: timestamp(),
+        })
+    }
+
+    /// Whether the modifier with the xkb name `modifier` is held or locked.
+    pub fn modifier_active(&self, modifier: &str) -> bool {
+        self.state
+            .mod_name_is_active(modifier, STATE_MODS_EFFECTIVE)
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            depressed: self.state.serialize_mods(STATE_MODS_DEPRESSED),
+            latched: self.state.serialize_mods(STATE_MODS_LATCHED),
+            locked: self.state.serialize_mods(STATE_MODS_LOCKED),
+        }
+    }
+}
+
+/// The compose state for the locale set in the environment, if there's a compose table for it.
+fn load_compose() -> Option<compose::State> {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .find_map(|var| std::env::var_os(var).filter(|locale| !locale.is_empty()))
+        .unwrap_or_else(|| "C".into());
+    match compose::Table::new_from_locale(
+        &Context::new(CONTEXT_NO_FLAGS),
+        &locale,
+        compose::COMPILE_NO_FLAGS,
+    ) {
+        Ok(table) => Some(compose::State::new(&table, compose::STATE_NO_FLAGS)),
+        Err(()) => {
+            warn!(
+                ?locale,
+                "no compose table for the locale, dead keys and the compose key won't combine"
+            );
+            None
+        }
+    }
+}
+
+/// Compile the keymap described by `config`, falling back to the evdev defaults if it's invalid.
+///
+/// Only fails if even the defaults can't be compiled, in which case no keymap is going to work.
+pub fn load_keymap(config: &KeymapConfig) -> Result<Keymap, EclipseError> {
+    compile_keymap(config, Context::new)
+}
+
+/// `load_keymap` with the xkb contexts `context` makes from the flags each one needs.
+fn compile_keymap(
+    config: &KeymapConfig,
+    context: impl Fn(ContextFlags) -> Context,
+) -> Result<Keymap, EclipseError> {
+    if let Some(keymap) = Keymap::new_from_names(
+        &context(CONTEXT_NO_FLAGS),
+        &config.xkb_rules,
+        &config.xkb_model,
+        &config.xkb_layout,
+        &config.xkb_variant,
+        config.xkb_options.clone(),
+        KEYMAP_COMPILE_NO_FLAGS,
+    ) {
+        return Ok(keymap);
+    }
+    warn!(
+        ?config,
+        "couldn't compile keymap, falling back to the evdev defaults"
+    );
+    // ignore the environment too in case that's where the bad names came from
+    Keymap::new_from_names(
+        &context(CONTEXT_NO_ENVIRONMENT_NAMES),
+        "evdev",
+        "",
+        "",
+        "",
+        None,
+        KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(EclipseError::Keymap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xkbcommon::xkb::{keysyms, CONTEXT_NO_DEFAULT_INCLUDES, KEYMAP_FORMAT_TEXT_V1};
+
+    fn us_keymap() -> Keymap {
+        load_keymap(&KeymapConfig {
+            xkb_layout: "us".to_string(),
+            ..KeymapConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn evdev_keycodes_are_offset_for_xkb() {
+        let state = State::new(&us_keymap());
+        let key_a = input_event_codes::KEY_A!();
+        assert_eq!(state.key_get_one_sym(xkb_keycode(key_a)), keysyms::KEY_a);
+        assert_eq!(evdev_keycode(xkb_keycode(key_a)), key_a);
+    }
+
+    #[test]
+    fn repeated_presses_are_sent_once() {
+        let mut state = KeyboardState::new(
+            vec![us_keymap()],
+            KeyRepeatConfig::default(),
+            Vec::new(),
+            Vec::new(),
+        );
+        let key_a = input_event_codes::KEY_A!();
+        assert_eq!(state.key(None, key_a, true).inputs.len(), 1);
+        assert!(state.key(None, key_a, true).inputs.is_empty());
+        assert_eq!(state.key(None, key_a, false).inputs.len(), 1);
+        assert!(state.key(None, key_a, false).inputs.is_empty());
+    }
+
+    #[test]
+    fn releasing_everything_releases_inhibited_modifiers() {
+        let mut state = KeyboardState::new(
+            vec![us_keymap()],
+            KeyRepeatConfig::default(),
+            Vec::new(),
+            vec![InhibitedKey::Keysym(keysyms::KEY_Super_L)],
+        );
+        let key_super = input_event_codes::KEY_LEFTMETA!();
+        assert!(state.key(None, key_super, true).inputs.is_empty());
+        assert!(state.modifier_active(MOD_NAME_LOGO));
+        assert!(state.release_all().is_empty());
+        assert!(!state.modifier_active(MOD_NAME_LOGO));
+        assert!(state.inhibited_held.is_empty());
+        // the key's release afterwards has nothing left to release
+        assert!(state.key(None, key_super, false).inputs.is_empty());
+    }
+
+    #[test]
+    fn inhibited_keys_dont_compose() {
+        let mut state = KeyboardState::new(
+            vec![us_keymap()],
+            KeyRepeatConfig::default(),
+            Vec::new(),
+            vec![InhibitedKey::Code(input_event_codes::KEY_APOSTROPHE!())],
+        );
+        let table = compose::Table::new_from_buffer(
+            &Context::new(CONTEXT_NO_FLAGS),
+            "<apostrophe> <a> : \"á\"\n",
+            "C",
+            compose::FORMAT_TEXT_V1,
+            compose::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        state.compose = Some(compose::State::new(&table, compose::STATE_NO_FLAGS));
+        state.key(None, input_event_codes::KEY_APOSTROPHE!(), true);
+        state.key(None, input_event_codes::KEY_APOSTROPHE!(), false);
+        let typed = state.key(None, input_event_codes::KEY_A!(), true).inputs;
+        assert_eq!(typed[0].text.as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn invalid_keymaps_fall_back_to_evdev() {
+        let invalid = KeymapConfig {
+            xkb_layout: "not_a_layout".to_string(),
+            ..KeymapConfig::default()
+        };
+        let fallback = load_keymap(&invalid).unwrap();
+        let evdev = compile_keymap(&KeymapConfig::default(), |flags| {
+            Context::new(flags | CONTEXT_NO_ENVIRONMENT_NAMES)
+        })
+        .unwrap();
+        assert_eq!(
+            fallback.get_as_string(KEYMAP_FORMAT_TEXT_V1),
+            evdev.get_as_string(KEYMAP_FORMAT_TEXT_V1)
+        );
+    }
+
+    #[test]
+    fn no_keymap_without_the_evdev_defaults() {
+        // without any include paths not even the defaults can be found
+        let result = compile_keymap(&KeymapConfig::default(), |flags| {
+            Context::new(flags | CONTEXT_NO_DEFAULT_INCLUDES)
+        });
+        assert!(matches!(result, Err(EclipseError::Keymap)));
+    }
+}
@@ -0,0 +1,1648 @@
+//! Eclipse's libinput to pulse bridge, which the `eclipse` binary runs with `run` and other
+//! Stardust clients can embed with `EclipseBridge`.
+
+mod check;
+pub mod cli;
+pub mod config;
+mod control;
+pub mod error;
+pub mod events;
+mod gamepad;
+mod heartbeat;
+mod idle;
+mod keyboard;
+mod metrics;
+mod pointer;
+mod recording;
+mod routing;
+mod scroll;
+mod seat;
+mod session;
+
+pub use routing::ReceiverEntry;
+
+use cli::Args;
+use color_eyre::eyre::{eyre, Result};
+use config::{BroadcastMode, CaptureConfig, Config};
+use control::{Command, ControlRequest, Reply};
+use error::EclipseError;
+use events::{
+    CaptureEvent, CursorWarp, GamepadEvent, GestureEvent, HoverPhase, IdleEvent, KeyboardInput,
+    MouseInput, PauseEvent, SwitchEvent, TabletEvent, TabletPadEvent, TextEvent, TouchEvent,
+    CAPTURE_MASK, CURSOR_WARP_MASK, GAMEPAD_MASK, GESTURE_MASK, IDLE_MASK, PAUSE_MASK, SWITCH_MASK,
+    TABLET_MASK, TABLET_PAD_MASK, TEXT_MASK, TOUCH_MASK,
+};
+use idle::IdleTracker;
+use keyboard::Action;
+use metrics::METRICS;
+use pointer::{ClickCounter, Cursor, DragTracker, MotionAccumulator};
+use recording::{Recorded, RecordedEvent, Recorder};
+use routing::{
+    closest_receiver, create_pulse_sender, forward_all, select_receivers, send_to, send_to_all,
+    update_pulse_sender, Batch, CursorWarpHandler, ReceiverHandler,
+};
+use seat::{
+    list_devices, poll_timeout, read_seat, HotkeyAction, InputSenders, SeatChannels, SeatControl,
+};
+use session::SessionActivity;
+use stardust_xr_fusion::client::{Client, FrameInfo, RootHandler};
+use stardust_xr_fusion::core::values::Transform;
+use stardust_xr_fusion::data::{PulseReceiver, PulseSender};
+use stardust_xr_fusion::fields::{Field, SphereField, UnknownField};
+use stardust_xr_fusion::spatial::Spatial;
+use stardust_xr_fusion::{HandlerWrapper, Mutex as FusionMutex};
+use stardust_xr_molecules::keyboard::KEYBOARD_MASK;
+use stardust_xr_molecules::mouse::{MouseEvent, MOUSE_MASK};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot::error::TryRecvError;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, trace, warn};
+
+/// Run Eclipse the way the command line says until Ctrl+C or the quit hotkey, reconnecting to the
+/// server whenever the connection is lost.
+pub async fn run(args: Args) -> Result<()> {
+    if args.check {
+        return check::run().await;
+    }
+    let mut config = args.load_config()?;
+    if args.list_devices {
+        return list_devices(&config);
+    }
+
+    let recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+    let replay = args.replay.as_deref().map(recording::load).transpose()?;
+    let replay_speed = args.replay_speed;
+    if !(replay_speed > 0.0 && replay_speed.is_finite()) {
+        return Err(eyre!("--replay-speed has to be more than 0"));
+    }
+    let (input, mut receivers) =
+        Input::start(&config, replay.map(|records| (records, replay_speed))).await?;
+    receivers.recorder = recorder;
+    if config.metrics_interval > 0 {
+        tokio::spawn(metrics::log_periodically(Duration::from_secs(
+            config.metrics_interval,
+        )));
+    }
+    if config.heartbeat_interval > 0 {
+        tokio::spawn(heartbeat::log_periodically(Duration::from_secs(
+            config.heartbeat_interval,
+        )));
+    }
+    // commands wait in the channel while there's no connection, reloads apply to reconnects too
+    let (control_tx, mut control_rx) = tokio::sync::mpsc::channel(8);
+    let control_socket = config.control_socket.clone();
+    if let Some(path) = &control_socket {
+        tokio::spawn(control::serve(
+            path.clone(),
+            args.clone(),
+            control_tx.clone(),
+        ));
+    }
+    tokio::spawn(control::reload_on_sighup(args.clone(), control_tx));
+
+    // keep input flowing across server restarts, the libinput thread never notices
+    let mut retry_delay = RECONNECT_DELAY_MIN;
+    let connection = 'connection: loop {
+        if args.dry_run {
+            dry_run(
+                receivers,
+                &mut control_rx,
+                &input.seat_control,
+                &mut config,
+                &input.quit,
+            )
+            .await;
+            break None;
+        }
+        let connection = tokio::select! {
+            biased;
+            _ = shutdown_requested(&input.quit) => {
+                // nothing's left to receive the releases, don't let the libinput thread wait on it
+                drop(receivers);
+                break None;
+            }
+            connection = Client::connect_with_async_loop() => connection,
+        };
+        let (client, event_loop) = match connection {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    ?retry_delay,
+                    "couldn't connect to the stardust server, retrying"
+                );
+                receivers.discard_pending();
+                tokio::select! {
+                    biased;
+                    _ = shutdown_requested(&input.quit) => {
+                        drop(receivers);
+                        break None;
+                    }
+                    _ = tokio::time::sleep(retry_delay) => (),
+                }
+                retry_delay = (retry_delay * 2).min(RECONNECT_DELAY_MAX);
+                continue;
+            }
+        };
+        info!("connected to the stardust server");
+        retry_delay = RECONNECT_DELAY_MIN;
+
+        let eclipse = client.wrap_root(Eclipse::create(&client, &config, receivers)?)?;
+        let flush_task = spawn_flush_task(&eclipse, config.flush_rate);
+        let mut event_loop = event_loop;
+        let result = loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_requested(&input.quit) => break 'connection Some((client, eclipse)),
+                result = &mut event_loop => break result,
+                Some(ControlRequest { command, reply }) = control_rx.recv() => match command {
+                    Command::Key(key) => input.seat_control.inject_key(key, reply),
+                    command => {
+                        if let Command::Reload(new_config) = &command {
+                            input.seat_control.send(new_config);
+                        }
+                        let _ = reply.send(eclipse.lock().control(command, &mut config));
+                    }
+                },
+            }
+        };
+        warn!(?result, "lost connection to the stardust server");
+        if let Some(flush_task) = flush_task {
+            flush_task.abort();
+            let _ = flush_task.await;
+        }
+        drop(client);
+        receivers = Arc::try_unwrap(eclipse)
+            .map_err(|_| eyre!("Eclipse is still in use after disconnecting"))?
+            .into_inner()
+            .into_receivers();
+        receivers.discard_pending();
+    };
+
+    // frames keep forwarding the releases the libinput thread sends on its way out while it stops
+    input.stop().await?;
+    if let Some(path) = &control_socket {
+        control::remove_socket(path);
+    }
+    if let Some((client, eclipse)) = connection {
+        finish_sending(&client, &eclipse).await?;
+    }
+
+    Ok(())
+}
+
+/// Eclipse's input bridge running inside another Stardust client, reading input like the `eclipse`
+/// binary does and sending it to receivers through the client's connection.
+///
+/// It handles the client's frames to send input with, so the client can't wrap its root in another
+/// handler. There's no reconnecting, control socket or recording, those are up to the client.
+pub struct EclipseBridge {
+    client: Arc<Client>,
+    eclipse: Arc<FusionMutex<Eclipse>>,
+    input: Input,
+    flush_task: Option<JoinHandle<()>>,
+}
+impl EclipseBridge {
+    /// Start reading input with `config` and sending it to the receivers on `client`'s server.
+    pub async fn spawn(client: Arc<Client>, config: Config) -> Result<Self, EclipseError> {
+        let (input, receivers) = Input::start(&config, None).await?;
+        let eclipse = client.wrap_root(Eclipse::create(&client, &config, receivers)?)?;
+        let flush_task = spawn_flush_task(&eclipse, config.flush_rate);
+        Ok(EclipseBridge {
+            client,
+            eclipse,
+            input,
+            flush_task,
+        })
+    }
+
+    /// The receivers connected to each of the pulse senders, by the kind of input the sender sends
+    /// like `mouse` and then by UID.
+    pub fn receivers(&self) -> BTreeMap<String, BTreeMap<String, ReceiverEntry>> {
+        self.eclipse.lock().receiver_registry()
+    }
+
+    /// Stop reading input and wait until everything read before, like the releases of anything
+    /// still held, has been sent.
+    pub async fn stop(self) -> Result<(), EclipseError> {
+        if let Some(flush_task) = self.flush_task {
+            flush_task.abort();
+            let _ = flush_task.await;
+        }
+        // frames keep forwarding the releases the libinput thread sends on its way out while it stops
+        self.input.stop().await?;
+        finish_sending(&self.client, &self.eclipse).await
+    }
+}
+
+/// Send what's left after the input threads stopped and wait until the server has it all.
+async fn finish_sending(
+    client: &Client,
+    eclipse: &FusionMutex<Eclipse>,
+) -> Result<(), EclipseError> {
+    {
+        let mut eclipse = eclipse.lock();
+        eclipse.send_pending_events();
+        eclipse.cancel_pending_click();
+    }
+    // messages go out in order, so once this is answered everything before it was sent too
+    let root = client.get_root();
+    root.get_position_rotation_scale(root)?.await?;
+    Ok(())
+}
+
+/// Also send input `flush_rate` times a second between frames, if it's more than 0.
+///
+/// This runs on the same thread as the client's event loop and only touches the pulse senders with
+/// Eclipse locked, so it never overlaps with a frame.
+fn spawn_flush_task(
+    eclipse: &Arc<FusionMutex<Eclipse>>,
+    flush_rate: u32,
+) -> Option<JoinHandle<()>> {
+    (flush_rate > 0).then(|| {
+        let eclipse = eclipse.clone();
+        let period = Duration::from_secs_f64(1.0 / flush_rate as f64);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                eclipse.lock().send_pending_events();
+            }
+        })
+    })
+}
+
+/// The threads reading input devices or a replay, which send what they read to `InputReceivers`.
+struct Input {
+    threads: Vec<JoinHandle<()>>,
+    gamepad_thread: Option<JoinHandle<()>>,
+    /// Dropping these stops the threads, waking them up if they're waiting for input.
+    stop_libinput: UnixStream,
+    stop_gamepads: UnixStream,
+    seat_control: SeatControl,
+    /// Notified when the quit hotkey is pressed.
+    quit: Arc<Notify>,
+}
+impl Input {
+    /// Start reading the seats and gamepads in `config`, or play back a recording at a speed
+    /// instead, and wait until libinput has opened the devices.
+    async fn start(
+        config: &Config,
+        replay: Option<(Vec<Recorded>, f64)>,
+    ) -> Result<(Self, InputReceivers), EclipseError> {
+        let (stop_libinput, libinput_stopped) = UnixStream::pair()?;
+
+        let forward = config.forward;
+        // a channel can't have no room at all
+        let (mouse_event_tx, mouse_event_rx) =
+            input_channel(forward.pointer, config.channel_capacity.mouse.max(1));
+        let (keyboard_event_tx, keyboard_event_rx) =
+            input_channel(forward.keyboard, config.channel_capacity.keyboard.max(1));
+        let (touch_event_tx, touch_event_rx) = input_channel(forward.touch, 64);
+        let (tablet_event_tx, tablet_event_rx) = input_channel(forward.tablet, 64);
+        let (tablet_pad_event_tx, tablet_pad_event_rx) = input_channel(forward.tablet, 64);
+        let (gesture_event_tx, gesture_event_rx) = input_channel(forward.gesture, 64);
+        let reads_gamepads = config.gamepad.enabled && replay.is_none();
+        let (gamepad_event_tx, gamepad_event_rx) = input_channel(reads_gamepads, 64);
+        let (switch_event_tx, switch_event_rx) = input_channel(forward.switch, 64);
+        let (action_tx, action_rx) = tokio::sync::mpsc::channel(8);
+        let quit = Arc::new(Notify::new());
+        let receivers = InputReceivers {
+            mouse: mouse_event_rx,
+            keyboard: keyboard_event_rx,
+            touch: touch_event_rx,
+            tablet: tablet_event_rx,
+            tablet_pad: tablet_pad_event_rx,
+            gesture: gesture_event_rx,
+            gamepad: gamepad_event_rx,
+            switch: switch_event_rx,
+            actions: action_rx,
+            recorder: None,
+        };
+        let senders = InputSenders {
+            mouse: mouse_event_tx,
+            keyboard: keyboard_event_tx,
+            touch: touch_event_tx,
+            tablet: tablet_event_tx,
+            tablet_pad: tablet_pad_event_tx,
+            gesture: gesture_event_tx,
+            switch: switch_event_tx,
+            actions: action_tx,
+            quit: quit.clone(),
+            session: if config.active_session_only {
+                SessionActivity::follow()
+            } else {
+                SessionActivity::always()
+            },
+        };
+        let mut seat_control = SeatControl::default();
+        let mut libinput_ready = Vec::new();
+        let mut libinput_threads = Vec::new();
+        if let Some((records, replay_speed)) = replay {
+            // a replay stands in for libinput entirely, no devices are opened
+            let (mouse_tx, keyboard_tx) = (senders.mouse.clone(), senders.keyboard.clone());
+            let stopped = libinput_stopped.try_clone()?;
+            libinput_threads.push(tokio::task::spawn_blocking(move || {
+                recording::replay(
+                    records,
+                    replay_speed,
+                    mouse_tx.as_ref(),
+                    keyboard_tx.as_ref(),
+                    &stopped,
+                )
+            }));
+        } else {
+            if !config.device_paths.is_empty() && !config.extra_seats.is_empty() {
+                warn!("extra_seats are ignored when device_paths are set");
+            }
+            // every seat gets a thread of its own, they all send into the same channels
+            for seat_config in config.seat_configs() {
+                let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+                let (config_tx, config_rx) = std::sync::mpsc::channel();
+                let (key_tx, key_rx) = std::sync::mpsc::channel();
+                let (config_wake, config_woken) = UnixStream::pair()?;
+                let stopped = libinput_stopped.try_clone()?;
+                let senders = senders.clone();
+                seat_control.seats.push(SeatChannels {
+                    seat: seat_config.seat.clone(),
+                    config_tx,
+                    key_tx,
+                    wake: config_wake,
+                });
+                libinput_ready.push(ready_rx);
+                libinput_threads.push(tokio::task::spawn_blocking(move || {
+                    read_seat(
+                        seat_config,
+                        senders,
+                        ready_tx,
+                        stopped,
+                        config_rx,
+                        key_rx,
+                        config_woken,
+                    )
+                }));
+            }
+        }
+        let session = senders.session.clone();
+        drop(senders);
+        drop(libinput_stopped);
+
+        for ready in libinput_ready {
+            ready.await.map_err(|_| EclipseError::InputThread)??;
+        }
+
+        // libinput doesn't handle gamepads, so they're read from evdev on a thread of their own
+        let (stop_gamepads, gamepads_stopped) = UnixStream::pair()?;
+        let gamepad_thread = gamepad_event_tx.map(|gamepad_event_tx| {
+            let gamepad_config = config.gamepad;
+            let devices = config.devices.clone();
+            tokio::task::spawn_blocking(move || {
+                gamepad::read_gamepads(
+                    gamepad_config,
+                    devices,
+                    gamepad_event_tx,
+                    session,
+                    gamepads_stopped,
+                )
+            })
+        });
+        Ok((
+            Input {
+                threads: libinput_threads,
+                gamepad_thread,
+                stop_libinput,
+                stop_gamepads,
+                seat_control,
+                quit,
+            },
+            receivers,
+        ))
+    }
+
+    /// Stop the threads and wait for them, sending the releases for anything held on their way out.
+    async fn stop(self) -> Result<(), EclipseError> {
+        drop(self.stop_libinput);
+        drop(self.stop_gamepads);
+        for thread in self.threads {
+            thread.await.map_err(|_| EclipseError::InputThread)?;
+        }
+        if let Some(gamepad_thread) = self.gamepad_thread {
+            gamepad_thread
+                .await
+                .map_err(|_| EclipseError::InputThread)?;
+        }
+        Ok(())
+    }
+}
+
+/// A channel for a kind of input, or none at all if it isn't forwarded.
+fn input_channel<T>(forwarded: bool, capacity: usize) -> (Option<Sender<T>>, Option<Receiver<T>>) {
+    forwarded
+        .then(|| tokio::sync::mpsc::channel(capacity))
+        .unzip()
+}
+
+/// How often a dry run takes the events out of the channels, about as often as a server's frames.
+const DRY_RUN_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Wait for Ctrl+C or the quit hotkey.
+async fn shutdown_requested(quit: &Notify) {
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = quit.notified() => info!("quitting for the quit hotkey"),
+    }
+}
+
+/// Log the events from the libinput threads instead of sending them anywhere until shutdown, to
+/// tell whether devices are read at all apart from whether events reach receivers.
+async fn dry_run(
+    mut receivers: InputReceivers,
+    control_rx: &mut Receiver<ControlRequest>,
+    seat_control: &SeatControl,
+    config: &mut Config,
+    quit: &Notify,
+) {
+    info!("dry run, logging events instead of connecting to the stardust server");
+    let mut interval = tokio::time::interval(DRY_RUN_FRAME_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_requested(quit) => return,
+            Some(ControlRequest { command, reply }) = control_rx.recv() => {
+                let result = match command {
+                    Command::Reload(new_config) => {
+                        seat_control.send(&new_config);
+                        *config = *new_config;
+                        info!("reloaded config");
+                        Ok(serde_json::Value::Null)
+                    }
+                    Command::Key(key) => {
+                        seat_control.inject_key(key, reply);
+                        continue;
+                    }
+                    _ => Err("There are no receivers in a dry run".to_string()),
+                };
+                let _ = reply.send(result);
+            }
+            _ = interval.tick() => receivers.log_pending(),
+        }
+    }
+}
+
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+/// The receiving ends of the channels the libinput thread sends events through, `None` for the
+/// kinds of input that aren't forwarded.
+struct InputReceivers {
+    mouse: Option<Receiver<MouseInput>>,
+    keyboard: Option<Receiver<KeyboardInput>>,
+    touch: Option<Receiver<TouchEvent>>,
+    tablet: Option<Receiver<TabletEvent>>,
+    tablet_pad: Option<Receiver<TabletPadEvent>>,
+    gesture: Option<Receiver<GestureEvent>>,
+    gamepad: Option<Receiver<GamepadEvent>>,
+    switch: Option<Receiver<SwitchEvent>>,
+    actions: Receiver<HotkeyAction>,
+    /// Where mouse and keyboard events are written as they're received, if they're recorded.
+    recorder: Option<Recorder>,
+}
+impl InputReceivers {
+    /// Throw away everything that's been queued up, so the libinput thread doesn't block while
+    /// there's nowhere to send events and stale input isn't replayed into a new session.
+    fn discard_pending(&mut self) {
+        self.discard_events();
+        std::iter::from_fn(|| self.actions.try_recv().ok()).for_each(drop);
+    }
+
+    /// Throw away the queued up events, leaving the hotkey actions.
+    fn discard_events(&mut self) {
+        fn discard<T>(receiver: Option<&mut Receiver<T>>) -> u64 {
+            receiver.map_or(0, |receiver| {
+                std::iter::from_fn(|| receiver.try_recv().ok()).count() as u64
+            })
+        }
+        METRICS.discarded(
+            discard(self.mouse.as_mut())
+                + discard(self.keyboard.as_mut())
+                + discard(self.touch.as_mut())
+                + discard(self.tablet.as_mut())
+                + discard(self.tablet_pad.as_mut())
+                + discard(self.gesture.as_mut())
+                + discard(self.gamepad.as_mut())
+                + discard(self.switch.as_mut()),
+        );
+    }
+
+    /// Log everything that's been queued up instead of sending it, for dry runs.
+    fn log_pending(&mut self) {
+        fn log<T: std::fmt::Debug>(receiver: Option<&mut Receiver<T>>, kind: &str) {
+            let Some(receiver) = receiver else {
+                return;
+            };
+            while let Ok(event) = receiver.try_recv() {
+                info!(kind, ?event, "input event");
+            }
+        }
+        while let Some(event) = try_recv(&mut self.mouse) {
+            self.record(RecordedEvent::Mouse(&event));
+            info!(kind = "mouse", ?event, "input event");
+        }
+        while let Some(event) = try_recv(&mut self.keyboard) {
+            self.record(RecordedEvent::Keyboard(&event));
+            info!(kind = "keyboard", ?event, "input event");
+        }
+        self.flush_recording();
+        log(self.touch.as_mut(), "touch");
+        log(self.tablet.as_mut(), "tablet");
+        log(self.tablet_pad.as_mut(), "tablet_pad");
+        log(self.gesture.as_mut(), "gesture");
+        log(self.gamepad.as_mut(), "gamepad");
+        log(self.switch.as_mut(), "switch");
+        while let Ok(HotkeyAction { action, .. }) = self.actions.try_recv() {
+            info!(?action, "hotkey pressed");
+        }
+    }
+
+    /// Add `event` to the recording, giving up on recording if the file can't be written.
+    fn record(&mut self, event: RecordedEvent) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if let Err(err) = recorder.record(event) {
+            warn!(?err, "couldn't write to the recording, stopping it");
+            self.recorder = None;
+        }
+    }
+
+    fn flush_recording(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        if let Err(err) = recorder.flush() {
+            warn!(?err, "couldn't write to the recording, stopping it");
+            self.recorder = None;
+        }
+    }
+}
+
+/// The next event queued up in a channel that might not exist.
+fn try_recv<T>(receiver: &mut Option<Receiver<T>>) -> Option<T> {
+    receiver.as_mut()?.try_recv().ok()
+}
+
+/// How long after connecting Eclipse waits for the preferred receiver before warning it's missing.
+const PREFERRED_RECEIVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The receiver the config says input should go to whenever it's connected.
+struct PreferredReceiver {
+    uid: String,
+    /// When Eclipse started waiting for it to connect, `None` once it has or it's been warned about.
+    waiting_since: Option<Instant>,
+    /// Whether it was a connected keyboard receiver last frame, so it takes the focus when it connects.
+    keyboard_connected: bool,
+}
+impl PreferredReceiver {
+    fn new(uid: String) -> Self {
+        PreferredReceiver {
+            uid,
+            waiting_since: Some(Instant::now()),
+            keyboard_connected: false,
+        }
+    }
+}
+
+/// A left click whose raycast for click to focus hasn't been answered yet.
+struct PendingClick {
+    hit: tokio::sync::oneshot::Receiver<Option<String>>,
+    /// The click and every mouse event after it.
+    events: Vec<MouseInput>,
+}
+
+struct Eclipse {
+    broadcast_mode: BroadcastMode,
+    /// Send each frame's mouse and keyboard events as batches instead of one pulse per event.
+    batch_events: bool,
+    button_names: bool,
+    middle_click_paste: bool,
+    hover_events: bool,
+    mouse_batch: Batch<MouseInput>,
+    keyboard_batch: Batch<KeyboardInput>,
+    receivers: InputReceivers,
+    motion_accumulator: MotionAccumulator,
+    /// The pulse senders are `None` for the kinds of input that aren't forwarded.
+    mouse_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// UID of the mouse receiver whose field is closest to the mouse pulse sender, refreshed once per frame.
+    closest_mouse_receiver: Arc<Mutex<Option<String>>>,
+    closest_mouse_receiver_task: Option<JoinHandle<()>>,
+    preferred_receiver: Option<PreferredReceiver>,
+    /// UID of the receiver keyboard input goes to, which only changes when it's explicitly moved.
+    focused_keyboard: Option<String>,
+    /// UID of the receiver mouse input goes to, which follows the pointer around.
+    focused_pointer: Option<String>,
+    /// Mouse buttons held down, which keep the pointer focus where the press went until released.
+    held_buttons: HashSet<u32>,
+    pending_click: Option<PendingClick>,
+    capture_config: CaptureConfig,
+    /// Whether input is captured, which keeps the focus where it is until it's released.
+    captured: bool,
+    /// Whether forwarding input is paused, which throws away everything that comes in meanwhile.
+    paused: bool,
+    /// `None` if the cursor is turned off.
+    cursor: Option<Cursor>,
+    /// `None` if clicks aren't counted.
+    click_counter: Option<ClickCounter>,
+    /// `None` if drags aren't detected.
+    drag_tracker: Option<DragTracker>,
+    keyboard_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    touch_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    tablet_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    tablet_pad_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    gesture_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    gamepad_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    switch_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// `None` without a `toggle_capture` hotkey or control socket to capture input with.
+    capture_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// `None` if receivers don't get told about idling.
+    idle_tracker: Option<IdleTracker>,
+    idle_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// `None` without a `toggle_pause` hotkey.
+    pause_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// `None` if text isn't sent.
+    text_pulse_sender: Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    /// What the pulse senders are created on, including the ones a reload turns on later.
+    pulse_sender_root: Spatial,
+    /// Takes cursor warps from receivers, which come through `cursor_warps`.
+    _cursor_warp_receiver: HandlerWrapper<PulseReceiver, CursorWarpHandler>,
+    _cursor_warp_field: SphereField,
+    cursor_warps: tokio::sync::mpsc::UnboundedReceiver<(String, CursorWarp)>,
+}
+impl Eclipse {
+    pub fn create(
+        client: &Client,
+        config: &Config,
+        receivers: InputReceivers,
+    ) -> Result<Self, EclipseError> {
+        let pulse_sender_root = Spatial::create(client.get_root(), Transform::identity(), false)?;
+        // only the kinds of input there's a channel for get a sender, so receivers for the others
+        // never see Eclipse, and the same goes for the features that are off
+        let input_pulse_sender = |forwarded: bool, mask: &[u8], kind| {
+            forwarded
+                .then(|| create_pulse_sender(&pulse_sender_root, mask, kind))
+                .transpose()
+        };
+        let mouse_pulse_sender =
+            input_pulse_sender(receivers.mouse.is_some(), &MOUSE_MASK, "mouse")?;
+        let keyboard_pulse_sender =
+            input_pulse_sender(receivers.keyboard.is_some(), &KEYBOARD_MASK, "keyboard")?;
+        let touch_pulse_sender =
+            input_pulse_sender(receivers.touch.is_some(), &TOUCH_MASK, "touch")?;
+        let tablet_pulse_sender =
+            input_pulse_sender(receivers.tablet.is_some(), &TABLET_MASK, "tablet")?;
+        let tablet_pad_pulse_sender = input_pulse_sender(
+            receivers.tablet_pad.is_some(),
+            &TABLET_PAD_MASK,
+            "tablet_pad",
+        )?;
+        let gesture_pulse_sender =
+            input_pulse_sender(receivers.gesture.is_some(), &GESTURE_MASK, "gesture")?;
+        let gamepad_pulse_sender =
+            input_pulse_sender(receivers.gamepad.is_some(), &GAMEPAD_MASK, "gamepad")?;
+        let switch_pulse_sender =
+            input_pulse_sender(receivers.switch.is_some(), &SWITCH_MASK, "switch")?;
+        let capture_pulse_sender = input_pulse_sender(captures(config), &CAPTURE_MASK, "capture")?;
+        let idle_pulse_sender = input_pulse_sender(config.idle_timeout > 0, &IDLE_MASK, "idle")?;
+        let pause_pulse_sender = input_pulse_sender(
+            !config.hotkeys.toggle_pause.is_empty(),
+            &PAUSE_MASK,
+            "pause",
+        )?;
+        let text_pulse_sender = input_pulse_sender(config.text_events, &TEXT_MASK, "text")?;
+        let (cursor_warp_tx, cursor_warps) = tokio::sync::mpsc::unbounded_channel();
+        let cursor_warp_field = SphereField::create(client.get_root(), [0.0; 3], 0.0)?;
+        let cursor_warp_receiver = PulseReceiver::create(
+            client.get_root(),
+            Transform::identity(),
+            &cursor_warp_field,
+            &CURSOR_WARP_MASK,
+        )?
+        .wrap(CursorWarpHandler { tx: cursor_warp_tx })?;
+
+        Ok(Eclipse {
+            broadcast_mode: config.broadcast,
+            batch_events: config.batch_events,
+            button_names: config.button_names,
+            middle_click_paste: config.middle_click_paste,
+            hover_events: config.hover_events,
+            mouse_batch: Batch::new(&MOUSE_MASK),
+            keyboard_batch: Batch::new(&KEYBOARD_MASK),
+            receivers,
+            motion_accumulator: MotionAccumulator::default(),
+            mouse_pulse_sender,
+            closest_mouse_receiver: Arc::new(Mutex::new(None)),
+            closest_mouse_receiver_task: None,
+            preferred_receiver: config
+                .preferred_receiver
+                .clone()
+                .map(PreferredReceiver::new),
+            focused_keyboard: None,
+            focused_pointer: None,
+            held_buttons: HashSet::new(),
+            pending_click: None,
+            capture_config: config.capture,
+            captured: false,
+            paused: false,
+            cursor: config.cursor.enabled.then(|| Cursor::new(&config.cursor)),
+            click_counter: config
+                .clicks
+                .enabled
+                .then(|| ClickCounter::new(config.clicks)),
+            drag_tracker: config.drag.enabled.then(|| DragTracker::new(config.drag)),
+            keyboard_pulse_sender,
+            touch_pulse_sender,
+            tablet_pulse_sender,
+            tablet_pad_pulse_sender,
+            gesture_pulse_sender,
+            gamepad_pulse_sender,
+            switch_pulse_sender,
+            capture_pulse_sender,
+            idle_tracker: (config.idle_timeout > 0)
+                .then(|| IdleTracker::new(Duration::from_secs(config.idle_timeout))),
+            idle_pulse_sender,
+            pause_pulse_sender,
+            text_pulse_sender,
+            pulse_sender_root,
+            _cursor_warp_receiver: cursor_warp_receiver,
+            _cursor_warp_field: cursor_warp_field,
+            cursor_warps,
+        })
+    }
+
+    /// Give back the event receivers so they can be used with the next connection.
+    fn into_receivers(self) -> InputReceivers {
+        self.receivers
+    }
+
+    /// Measure how far every receiver's field is from the origin of `sender` again and keep it in
+    /// `handler`, so `closest_receiver` goes by where the receivers are now.
+    ///
+    /// All distance queries are sent before any of them are awaited so this takes a single round trip.
+    async fn measure_distances(sender: &PulseSender, handler: &FusionMutex<ReceiverHandler>) {
+        let queries = sender
+            .receivers()
+            .iter()
+            .filter_map(|(uid, (_receiver, field))| {
+                Some((uid.clone(), field.distance(sender, [0.0; 3]).ok()?))
+            })
+            .collect::<Vec<_>>();
+
+        for (uid, distance) in queries {
+            let Ok(distance) = distance.await else {
+                continue;
+            };
+            if let Some(entry) = handler.lock().receivers.get_mut(&uid) {
+                entry.distance = distance;
+            }
+        }
+    }
+
+    /// Find the UID of the keyboard receiver whose field is hit closest along the pointer's ray,
+    /// which points forward (-Z) from the mouse pulse sender.
+    async fn receiver_under_pointer(
+        mouse_sender: &PulseSender,
+        keyboard_sender: &PulseSender,
+    ) -> Option<String> {
+        let queries = keyboard_sender
+            .receivers()
+            .iter()
+            .filter_map(|(uid, (_receiver, field))| {
+                let ray_march = field
+                    .ray_march(mouse_sender, [0.0; 3], [0.0, 0.0, -1.0])
+                    .ok()?;
+                Some((uid.clone(), ray_march))
+            })
+            .collect::<Vec<_>>();
+
+        let mut closest: Option<(String, f32)> = None;
+        for (uid, ray_march) in queries {
+            let Ok(result) = ray_march.await else {
+                continue;
+            };
+            if result.hit()
+                && closest.as_ref().is_none_or(|(_, closest_distance)| {
+                    result.deepest_point_distance < *closest_distance
+                })
+            {
+                closest = Some((uid, result.deepest_point_distance));
+            }
+        }
+        closest.map(|(uid, _)| uid)
+    }
+
+    /// Focus whatever the pointer is over before `click` is sent, holding back mouse events until
+    /// the raycast is answered so they stay in order.
+    fn start_click_focus(
+        &mut self,
+        click: MouseInput,
+        mouse_sender: Arc<PulseSender>,
+        keyboard_sender: Arc<PulseSender>,
+    ) {
+        let (hit_tx, hit_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let hit = Eclipse::receiver_under_pointer(&mouse_sender, &keyboard_sender).await;
+            let _ = hit_tx.send(hit);
+        });
+        self.pending_click = Some(PendingClick {
+            hit: hit_rx,
+            events: vec![click],
+        });
+    }
+
+    /// Apply the pending click's focus change and send what it held back once its raycast is done.
+    fn resolve_pending_click(
+        &mut self,
+        receivers: &HashMap<String, (PulseReceiver, UnknownField), impl BuildHasher>,
+    ) {
+        let Some(pending) = &mut self.pending_click else {
+            return;
+        };
+        let hit = match pending.hit.try_recv() {
+            Ok(hit) => hit,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Closed) => None,
+        };
+        let events = std::mem::take(&mut pending.events);
+        self.pending_click = None;
+        // clicking on nothing leaves the focus where it was
+        if let Some(uid) = hit {
+            if self.focused_keyboard.as_ref() != Some(&uid) {
+                info!(receiver = uid, "focused keyboard receiver");
+                self.focused_keyboard = Some(uid);
+            }
+        }
+        for event in events {
+            self.handle_mouse_event(receivers, event);
+        }
+    }
+
+    fn send_batches(&mut self) {
+        if let Some(mouse_sender) = &self.mouse_pulse_sender {
+            self.mouse_batch.send(mouse_sender.node());
+        }
+        if let Some(keyboard_sender) = &self.keyboard_pulse_sender {
+            self.keyboard_batch.send(keyboard_sender.node());
+        }
+    }
+
+    /// Send what the pending click held back without waiting for its raycast.
+    fn cancel_pending_click(&mut self) {
+        let mouse_sender = self
+            .mouse_pulse_sender
+            .as_ref()
+            .map(|sender| sender.node().clone());
+        if let (Some(pending), Some(mouse_sender)) = (self.pending_click.take(), mouse_sender) {
+            let receivers = mouse_sender.receivers();
+            for event in pending.events {
+                self.send_mouse_event(&receivers, event);
+            }
+        }
+        self.send_batches();
+    }
+
+    fn handle_mouse_event(
+        &mut self,
+        receivers: &HashMap<String, (PulseReceiver, UnknownField), impl BuildHasher>,
+        mouse_event: MouseInput,
+    ) {
+        let left_click = !self.captured
+            && mouse_event
+                .event
+                .buttons_down
+                .as_ref()
+                .is_some_and(|buttons| buttons.contains(&input_event_codes::BTN_LEFT!()));
+        if let Some(pending) = &mut self.pending_click {
+            pending.events.push(mouse_event);
+        } else if let (true, Some(mouse_sender), Some(keyboard_sender)) = (
+            left_click,
+            &self.mouse_pulse_sender,
+            &self.keyboard_pulse_sender,
+        ) {
+            let (mouse_sender, keyboard_sender) =
+                (mouse_sender.node().clone(), keyboard_sender.node().clone());
+            self.start_click_focus(mouse_event, mouse_sender, keyboard_sender);
+        } else {
+            self.send_mouse_event(receivers, mouse_event);
+        }
+    }
+
+    fn update_closest_mouse_receiver(&mut self) {
+        if self
+            .closest_mouse_receiver_task
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+        {
+            return;
+        }
+        let Some(sender) = &self.mouse_pulse_sender else {
+            return;
+        };
+        let (node, handler) = (sender.node().clone(), sender.wrapped().clone());
+        let closest_mouse_receiver = self.closest_mouse_receiver.clone();
+        self.closest_mouse_receiver_task = Some(tokio::spawn(async move {
+            Eclipse::measure_distances(&node, &handler).await;
+            let closest = handler.lock().closest(None);
+            *closest_mouse_receiver.lock().unwrap() = closest;
+        }));
+    }
+
+    /// Keep the focus on a connected keyboard receiver, picking one if the focused one is gone.
+    fn update_keyboard_focus(&mut self) {
+        let Some(sender) = &self.keyboard_pulse_sender else {
+            return;
+        };
+        let receivers = sender.node().receivers();
+        if let Some(preferred) = &mut self.preferred_receiver {
+            let connected = receivers.contains_key(&preferred.uid);
+            let just_connected = connected && !preferred.keyboard_connected;
+            preferred.keyboard_connected = connected;
+            // the focus is held in place while captured
+            if just_connected
+                && !self.captured
+                && self.focused_keyboard.as_ref() != Some(&preferred.uid)
+            {
+                info!(
+                    receiver = preferred.uid,
+                    "focused preferred keyboard receiver"
+                );
+                self.focused_keyboard = Some(preferred.uid.clone());
+                return;
+            }
+        }
+        if self
+            .focused_keyboard
+            .as_ref()
+            .is_some_and(|uid| receivers.contains_key(uid))
+        {
+            return;
+        }
+        // lowest UID rather than the first in the map so it doesn't change as receivers come and go
+        self.focused_keyboard = self
+            .preferred_uid()
+            .filter(|uid| receivers.contains_key(uid))
+            .or_else(|| receivers.keys().min().cloned());
+        if let Some(uid) = &self.focused_keyboard {
+            info!(receiver = uid, "focused keyboard receiver");
+        }
+    }
+
+    fn preferred_uid(&self) -> Option<String> {
+        self.preferred_receiver
+            .as_ref()
+            .map(|preferred| preferred.uid.clone())
+    }
+
+    /// Warn once if the preferred receiver hasn't connected in time.
+    fn check_preferred_receiver(&mut self) {
+        let Some(preferred) = &mut self.preferred_receiver else {
+            return;
+        };
+        let Some(waiting_since) = preferred.waiting_since else {
+            return;
+        };
+        let connected = |sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>| {
+            sender
+                .as_ref()
+                .is_some_and(|sender| sender.node().receivers().contains_key(&preferred.uid))
+        };
+        if connected(&self.keyboard_pulse_sender) || connected(&self.mouse_pulse_sender) {
+            preferred.waiting_since = None;
+        } else if waiting_since.elapsed() > PREFERRED_RECEIVER_TIMEOUT {
+            warn!(
+                receiver = preferred.uid,
+                "the preferred receiver hasn't connected, routing input to others"
+            );
+            preferred.waiting_since = None;
+        }
+    }
+
+    /// Capture input for the focused receivers if it isn't already, otherwise release it.
+    fn toggle_capture(&mut self) {
+        self.set_captured(!self.captured);
+    }
+
+    fn set_captured(&mut self, captured: bool) {
+        if captured == self.captured {
+            return;
+        }
+        self.captured = captured;
+        // a captured pointer starts over from the middle, and so does the one given back
+        if let Some(cursor) = &mut self.cursor {
+            cursor.recenter();
+        }
+        info!(
+            keyboard_receiver = self.focused_keyboard,
+            pointer_receiver = self.focused_pointer,
+            captured,
+            "input capture changed"
+        );
+        send_to_all(&self.capture_pulse_sender, &CaptureEvent::new(captured));
+    }
+
+    /// Release the capture if a receiver it was holding input for went away.
+    fn update_capture(&mut self) {
+        if !self.captured {
+            return;
+        }
+        let connected = |sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>, uid| {
+            sender
+                .as_ref()
+                .is_some_and(|sender| sender.node().receivers().contains_key(uid))
+        };
+        let keyboard_gone = self
+            .focused_keyboard
+            .as_ref()
+            .is_some_and(|uid| !connected(&self.keyboard_pulse_sender, uid));
+        let pointer_gone = self
+            .focused_pointer
+            .as_ref()
+            .is_some_and(|uid| !connected(&self.mouse_pulse_sender, uid));
+        if keyboard_gone || pointer_gone {
+            self.set_captured(false);
+        }
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        self.paused = paused;
+        info!(paused, "input forwarding paused or resumed");
+        send_to_all(&self.pause_pulse_sender, &PauseEvent::new(paused));
+    }
+
+    /// Tell every idle receiver if input just went idle or resumed.
+    fn update_idle(&mut self) {
+        let Some(idle) = self.idle_tracker.as_mut().and_then(IdleTracker::update) else {
+            return;
+        };
+        debug!(idle, "input idle changed");
+        send_to_all(&self.idle_pulse_sender, &IdleEvent::new(idle));
+    }
+
+    /// Carry out a command from the control socket. Reloads also replace `config`, so the next
+    /// connection keeps using what was reloaded.
+    fn control(&mut self, command: Command, config: &mut Config) -> Reply {
+        match command {
+            Command::Receivers => {
+                let uids = |sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>| {
+                    sender.as_ref().map_or_else(Vec::new, |sender| {
+                        sender
+                            .lock_wrapped()
+                            .receivers
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                };
+                Ok(serde_json::json!({
+                    "keyboard": uids(&self.keyboard_pulse_sender),
+                    "mouse": uids(&self.mouse_pulse_sender),
+                    "all": self.receiver_registry(),
+                    "focused_keyboard": self.focused_keyboard,
+                    "focused_pointer": self.focused_pointer,
+                    "captured": self.captured,
+                    "paused": self.paused,
+                }))
+            }
+            // the focus is held in place while captured
+            Command::Focus(_) if self.captured => Err("Input is captured".to_string()),
+            Command::Focus(uid) => {
+                let Some(sender) = &self.keyboard_pulse_sender else {
+                    return Err("Keyboard input isn't forwarded".to_string());
+                };
+                if !sender.node().receivers().contains_key(&uid) {
+                    return Err(format!("No keyboard receiver {uid:?}"));
+                }
+                if self.focused_keyboard.as_ref() != Some(&uid) {
+                    info!(receiver = uid, "focused keyboard receiver");
+                    self.focused_keyboard = Some(uid);
+                }
+                Ok(serde_json::Value::Null)
+            }
+            Command::ToggleCapture => {
+                self.toggle_capture();
+                Ok(serde_json::json!({ "captured": self.captured }))
+            }
+            Command::Reload(new_config) => {
+                self.apply_config(&new_config);
+                *config = *new_config;
+                info!("reloaded config");
+                Ok(serde_json::Value::Null)
+            }
+            // handed to the libinput threads before it would get here, Eclipse has no keymap
+            Command::Key(_) => Err("Keys are injected by the libinput threads".to_string()),
+        }
+    }
+
+    /// Who gets input sent through `sender` when the `focused` receiver isn't connected to it: the
+    /// preferred receiver if it is, or else the closest one.
+    fn fallback_receiver(
+        &self,
+        sender: &HandlerWrapper<PulseSender, ReceiverHandler>,
+        focused: &Option<String>,
+    ) -> Option<String> {
+        if let Some(uid) = focused {
+            let kind = sender.lock_wrapped().kind;
+            let connected = self
+                .pulse_senders()
+                .into_iter()
+                .any(|sender| sender.lock_wrapped().receivers.contains_key(uid));
+            if connected {
+                debug!(
+                    receiver = uid,
+                    kind, "skipping the focused receiver, it doesn't take this kind of input"
+                );
+            } else {
+                debug!(
+                    receiver = uid,
+                    kind, "the focused receiver disconnected, sending to another"
+                );
+            }
+        }
+        closest_receiver(sender, self.preferred_uid().as_deref())
+    }
+
+    /// Every pulse sender's receivers, by the kind of input it sends and then by UID.
+    fn receiver_registry(&self) -> BTreeMap<String, BTreeMap<String, ReceiverEntry>> {
+        self.pulse_senders()
+            .into_iter()
+            .map(|sender| {
+                let handler = sender.lock_wrapped();
+                (handler.kind.to_string(), handler.receivers.clone())
+            })
+            .collect()
+    }
+
+    /// Every pulse sender there is, which each have their own receivers.
+    fn pulse_senders(&self) -> Vec<&HandlerWrapper<PulseSender, ReceiverHandler>> {
+        [
+            self.mouse_pulse_sender.as_ref(),
+            self.keyboard_pulse_sender.as_ref(),
+            self.touch_pulse_sender.as_ref(),
+            self.tablet_pulse_sender.as_ref(),
+            self.tablet_pad_pulse_sender.as_ref(),
+            self.gesture_pulse_sender.as_ref(),
+            self.gamepad_pulse_sender.as_ref(),
+            self.switch_pulse_sender.as_ref(),
+            self.capture_pulse_sender.as_ref(),
+            self.idle_pulse_sender.as_ref(),
+            self.pause_pulse_sender.as_ref(),
+            self.text_pulse_sender.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Use the settings from `config` that can change while running, the rest only apply once
+    /// Eclipse is restarted.
+    fn apply_config(&mut self, config: &Config) {
+        self.broadcast_mode = config.broadcast;
+        self.batch_events = config.batch_events;
+        self.button_names = config.button_names;
+        self.middle_click_paste = config.middle_click_paste;
+        self.hover_events = config.hover_events;
+        if self.preferred_uid() != config.preferred_receiver {
+            self.preferred_receiver = config
+                .preferred_receiver
+                .clone()
+                .map(PreferredReceiver::new);
+        }
+        self.capture_config = config.capture;
+        let root = &self.pulse_sender_root;
+        update_pulse_sender(
+            root,
+            &mut self.capture_pulse_sender,
+            captures(config),
+            &CAPTURE_MASK,
+            "capture",
+        );
+        update_pulse_sender(
+            root,
+            &mut self.idle_pulse_sender,
+            config.idle_timeout > 0,
+            &IDLE_MASK,
+            "idle",
+        );
+        update_pulse_sender(
+            root,
+            &mut self.pause_pulse_sender,
+            !config.hotkeys.toggle_pause.is_empty(),
+            &PAUSE_MASK,
+            "pause",
+        );
+        update_pulse_sender(
+            root,
+            &mut self.text_pulse_sender,
+            config.text_events,
+            &TEXT_MASK,
+            "text",
+        );
+        let timeout = Duration::from_secs(config.idle_timeout);
+        match &mut self.idle_tracker {
+            _ if config.idle_timeout == 0 => self.idle_tracker = None,
+            Some(idle_tracker) => idle_tracker.set_timeout(timeout),
+            None => self.idle_tracker = Some(IdleTracker::new(timeout)),
+        }
+        match &mut self.cursor {
+            _ if !config.cursor.enabled => self.cursor = None,
+            Some(cursor) => cursor.reconfigure(&config.cursor),
+            None => self.cursor = Some(Cursor::new(&config.cursor)),
+        }
+        match &mut self.click_counter {
+            _ if !config.clicks.enabled => self.click_counter = None,
+            Some(click_counter) => click_counter.config = config.clicks,
+            None => self.click_counter = Some(ClickCounter::new(config.clicks)),
+        }
+        match &mut self.drag_tracker {
+            _ if !config.drag.enabled => self.drag_tracker = None,
+            Some(drag_tracker) => drag_tracker.config = config.drag,
+            None => self.drag_tracker = Some(DragTracker::new(config.drag)),
+        }
+    }
+
+    /// The broadcast mode mouse and keyboard input is sent with right now.
+    fn input_broadcast_mode(&self) -> BroadcastMode {
+        if self.captured && self.capture_config.exclusive {
+            BroadcastMode::Off
+        } else {
+            self.broadcast_mode
+        }
+    }
+
+    /// Move the focus to the keyboard receiver after the focused one, in UID order.
+    fn focus_next(&mut self) {
+        let Some(sender) = &self.keyboard_pulse_sender else {
+            return;
+        };
+        let receivers = sender.node().receivers();
+        let mut uids = receivers.keys().collect::<Vec<_>>();
+        uids.sort();
+        let next = self
+            .focused_keyboard
+            .as_ref()
+            .and_then(|focused| uids.iter().position(|uid| *uid == focused))
+            .map_or(0, |index| index + 1);
+        self.focused_keyboard = uids.get(next).or(uids.first()).map(|uid| uid.to_string());
+        if let Some(uid) = &self.focused_keyboard {
+            info!(receiver = uid, "focused keyboard receiver");
+        }
+    }
+
+    /// Move the pointer focus to the mouse receiver closest to the pointer, unless a button is held
+    /// and the receiver it was pressed on is still connected.
+    fn update_pointer_focus(&mut self) {
+        let Some(sender) = &self.mouse_pulse_sender else {
+            return;
+        };
+        let receivers = sender.node().receivers();
+        let connected = |uid: &String| receivers.contains_key(uid);
+        if (self.captured || !self.held_buttons.is_empty())
+            && self.focused_pointer.as_ref().is_some_and(connected)
+        {
+            return;
+        }
+        let focused = self
+            .preferred_uid()
+            .filter(connected)
+            .or_else(|| self.closest_mouse_receiver.lock().unwrap().clone())
+            .filter(connected)
+            .or_else(|| self.focused_pointer.clone().filter(connected))
+            .or_else(|| receivers.keys().min().cloned());
+        if focused != self.focused_pointer {
+            debug!(receiver = focused, "focused pointer receiver");
+            if let Some(cursor) = self
+                .cursor
+                .as_mut()
+                .filter(|cursor| cursor.reset_on_focus && focused.is_some())
+            {
+                cursor.recenter();
+            }
+            // a receiver that's gone doesn't need telling, and losing the focus to no receiver
+            // still leaves the last one
+            if self.hover_events {
+                let hover = [
+                    (&self.focused_pointer, HoverPhase::Left),
+                    (&focused, HoverPhase::Entered),
+                ];
+                for (uid, phase) in hover {
+                    let Some((uid, (receiver, _))) =
+                        uid.as_ref().and_then(|uid| receivers.get_key_value(uid))
+                    else {
+                        continue;
+                    };
+                    let event = MouseInput {
+                        hover: Some(phase),
+                        ..MouseEvent::new(None, None, None, None, None).into()
+                    };
+                    send_to(&event, sender.node(), &[(uid.as_str(), receiver)]);
+                }
+            }
+            self.focused_pointer = focused;
+        }
+    }
+
+    fn send_mouse_event(
+        &mut self,
+        receivers: &HashMap<String, (PulseReceiver, UnknownField), impl BuildHasher>,
+        mut mouse_event: MouseInput,
+    ) {
+        // captured input is relative only, there's no pointer position for absolute motion to warp
+        if self.captured && mouse_event.position.take().is_some() && mouse_event.is_empty() {
+            return;
+        }
+        if self.button_names {
+            mouse_event.name_buttons();
+        }
+        if self.middle_click_paste {
+            mouse_event.mark_primary_paste();
+        }
+        if let Some(cursor) = &mut self.cursor {
+            mouse_event.cursor = Some(cursor.moved(&mouse_event).into());
+        }
+        if let Some(click_counter) = &mut self.click_counter {
+            mouse_event.click_count = click_counter.count(&mouse_event);
+        }
+        if let Some(drag_tracker) = &mut self.drag_tracker {
+            mouse_event.drag = drag_tracker.track(&mouse_event);
+        }
+        if let Some(buttons) = &mouse_event.event.buttons_down {
+            self.held_buttons.extend(buttons);
+        }
+        if let Some(buttons) = &mouse_event.event.buttons_up {
+            for button in buttons {
+                self.held_buttons.remove(button);
+            }
+        }
+        let targets = select_receivers(
+            self.input_broadcast_mode(),
+            receivers,
+            self.focused_pointer.as_deref(),
+            || {
+                let sender = self.mouse_pulse_sender.as_ref()?;
+                self.fallback_receiver(sender, &self.focused_pointer)
+            },
+        );
+        trace!(
+            receiver = self.focused_pointer,
+            targets = targets.len(),
+            event = ?mouse_event,
+            "sending mouse event"
+        );
+        if self.batch_events {
+            self.mouse_batch.push(&targets, mouse_event);
+        } else if let Some(sender) = &self.mouse_pulse_sender {
+            send_to(&mouse_event, sender.node(), &targets)
+        }
+    }
+
+    fn send_keyboard_event(
+        &mut self,
+        receivers: &HashMap<String, (PulseReceiver, UnknownField), impl BuildHasher>,
+        keyboard_event: KeyboardInput,
+    ) {
+        let targets = select_receivers(
+            self.input_broadcast_mode(),
+            receivers,
+            self.focused_keyboard.as_deref(),
+            || {
+                let sender = self.keyboard_pulse_sender.as_ref()?;
+                self.fallback_receiver(sender, &self.focused_keyboard)
+            },
+        );
+        trace!(
+            receiver = self.focused_keyboard,
+            targets = targets.len(),
+            event = ?keyboard_event,
+            "sending keyboard event"
+        );
+        if let (Some(text_sender), Some(text_event)) = (
+            &self.text_pulse_sender,
+            TextEvent::from_keyboard(&keyboard_event),
+        ) {
+            let text_receivers = text_sender.node().receivers();
+            let text_targets = select_receivers(
+                self.input_broadcast_mode(),
+                &text_receivers,
+                self.focused_keyboard.as_deref(),
+                || self.fallback_receiver(text_sender, &self.focused_keyboard),
+            );
+            send_to(&text_event, text_sender.node(), &text_targets);
+        }
+        if self.batch_events {
+            self.keyboard_batch.push(&targets, keyboard_event);
+        } else if let Some(sender) = &self.keyboard_pulse_sender {
+            send_to(&keyboard_event, sender.node(), &targets)
+        }
+    }
+}
+
+impl RootHandler for Eclipse {
+    fn frame(&mut self, _info: FrameInfo) {
+        let start = Instant::now();
+        self.update_closest_mouse_receiver();
+        self.send_pending_events();
+        self.update_idle();
+        METRICS.frame(start.elapsed());
+        let receiver_count = |sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>| {
+            sender
+                .as_ref()
+                .map_or(0, |sender| sender.node().receivers().len())
+        };
+        heartbeat::frame(
+            receiver_count(&self.mouse_pulse_sender),
+            receiver_count(&self.keyboard_pulse_sender),
+        );
+    }
+}
+impl Eclipse {
+    /// Move the cursors where receivers asked Eclipse to since the last frame.
+    fn apply_cursor_warps(&mut self) {
+        while let Ok((uid, warp)) = self.cursor_warps.try_recv() {
+            let Some(cursor) = &mut self.cursor else {
+                debug!(
+                    sender = uid,
+                    "ignoring cursor warp, the cursor is turned off"
+                );
+                continue;
+            };
+            debug!(sender = uid, ?warp, "warping cursor");
+            cursor.warp(warp.position.into(), warp.seat.as_deref());
+        }
+    }
+
+    /// Forward everything the libinput thread has sent since the last call.
+    fn send_pending_events(&mut self) {
+        self.apply_cursor_warps();
+        self.check_preferred_receiver();
+        self.update_capture();
+        self.update_keyboard_focus();
+        self.update_pointer_focus();
+        let mut pause_toggles = Vec::new();
+        while let Ok(HotkeyAction { action, time }) = self.receivers.actions.try_recv() {
+            match action {
+                // the focus is held in place while captured
+                Action::FocusNext if self.captured => (),
+                Action::FocusNext => self.focus_next(),
+                Action::ToggleCapture => self.toggle_capture(),
+                Action::NextKeymap => (),
+                Action::TogglePause => pause_toggles.push(time),
+                Action::Quit => (),
+            }
+        }
+        pause_toggles.sort_unstable();
+        self.forward_pending_events(pause_toggles);
+    }
+
+    /// Send everything that's come in since the last frame, pausing and resuming at the times in
+    /// `pause_toggles` along the way.
+    ///
+    /// The mouse and keyboard events from before pausing still go out and the ones from after
+    /// resuming do too, while the ones in between are thrown away instead of all arriving at once.
+    /// The other kinds of input don't have a time, they go by whether it's paused once the frame's
+    /// mouse and keyboard events are through.
+    fn forward_pending_events(&mut self, pause_toggles: Vec<u64>) {
+        let mut mouse_events = Vec::new();
+        while let Some(mouse_event) = try_recv(&mut self.receivers.mouse) {
+            self.receivers.record(RecordedEvent::Mouse(&mouse_event));
+            self.motion_accumulator
+                .accumulate(mouse_event, &mut mouse_events);
+        }
+        self.motion_accumulator.flush(&mut mouse_events);
+        let keyboard_events =
+            std::iter::from_fn(|| try_recv(&mut self.receivers.keyboard)).collect::<Vec<_>>();
+        for keyboard_event in &keyboard_events {
+            self.receivers
+                .record(RecordedEvent::Keyboard(keyboard_event));
+        }
+        self.receivers.flush_recording();
+
+        // a single snapshot of the receivers for the whole frame, anything connecting in the meantime
+        // gets events from the next frame on
+        let node = |sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>| {
+            sender.as_ref().map(|sender| sender.node().clone())
+        };
+        let mouse_sender = node(&self.mouse_pulse_sender);
+        let mouse_receivers = mouse_sender.as_ref().map(|sender| sender.receivers());
+        let keyboard_sender = node(&self.keyboard_pulse_sender);
+        let keyboard_receivers = keyboard_sender.as_ref().map(|sender| sender.receivers());
+        if let Some(mouse_receivers) = &mouse_receivers {
+            self.resolve_pending_click(mouse_receivers);
+        }
+        // interleave mouse and keyboard events the way they happened, so a key pressed before a click
+        // is still sent before it
+        let mut mouse_events = mouse_events.into_iter().peekable();
+        let mut keyboard_events = keyboard_events.into_iter().peekable();
+        let mut pause_toggles = pause_toggles.into_iter().peekable();
+        let mut discarded = 0;
+        loop {
+            let (mouse_first, time) = match (mouse_events.peek(), keyboard_events.peek()) {
+                (Some(mouse), Some(keyboard)) if mouse.time <= keyboard.time => (true, mouse.time),
+                (Some(_), Some(keyboard)) => (false, keyboard.time),
+                (Some(mouse), None) => (true, mouse.time),
+                (None, Some(keyboard)) => (false, keyboard.time),
+                (None, None) => break,
+            };
+            // the hotkey's own releases of what was held have its time and go out before pausing
+            while pause_toggles.next_if(|toggle| *toggle < time).is_some() {
+                self.set_paused(!self.paused);
+            }
+            if self.paused {
+                if mouse_first {
+                    mouse_events.next();
+                } else {
+                    keyboard_events.next();
+                }
+                discarded += 1;
+                continue;
+            }
+            // there are only events of a kind when there's a channel and so a sender for it
+            if mouse_first {
+                let mouse_event = mouse_events.next().unwrap();
+                if let Some(mouse_receivers) = &mouse_receivers {
+                    self.handle_mouse_event(mouse_receivers, mouse_event);
+                }
+            } else {
+                let keyboard_event = keyboard_events.next().unwrap();
+                if let Some(keyboard_receivers) = &keyboard_receivers {
+                    self.send_keyboard_event(keyboard_receivers, keyboard_event);
+                }
+            }
+        }
+        // sending the batches takes the receivers again, which could deadlock with a waiting writer
+        drop(mouse_receivers);
+        drop(keyboard_receivers);
+        self.send_batches();
+        for _ in pause_toggles {
+            self.set_paused(!self.paused);
+        }
+        METRICS.discarded(discarded);
+        if self.paused {
+            self.receivers.discard_events();
+            return;
+        }
+
+        let broadcast_mode = self.broadcast_mode;
+        let preferred = self.preferred_uid();
+        forward_all(
+            &self.touch_pulse_sender,
+            &mut self.receivers.touch,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+        forward_all(
+            &self.tablet_pulse_sender,
+            &mut self.receivers.tablet,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+        forward_all(
+            &self.tablet_pad_pulse_sender,
+            &mut self.receivers.tablet_pad,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+        forward_all(
+            &self.gesture_pulse_sender,
+            &mut self.receivers.gesture,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+        forward_all(
+            &self.gamepad_pulse_sender,
+            &mut self.receivers.gamepad,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+        forward_all(
+            &self.switch_pulse_sender,
+            &mut self.receivers.switch,
+            broadcast_mode,
+            preferred.as_deref(),
+        );
+    }
+}
+
+/// Whether input can be captured with `config`, by hotkey or through the control socket.
+fn captures(config: &Config) -> bool {
+    !config.hotkeys.toggle_capture.is_empty() || config.control_socket.is_some()
+}
@@ -0,0 +1,125 @@
+//! Counters for diagnosing throughput problems, logged periodically and reset each time.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tracing::info;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Keyboard,
+    Pointer,
+    Touch,
+    Tablet,
+    TabletPad,
+    Gesture,
+    Gamepad,
+    Switch,
+}
+impl InputKind {
+    const ALL: [InputKind; 8] = [
+        InputKind::Keyboard,
+        InputKind::Pointer,
+        InputKind::Touch,
+        InputKind::Tablet,
+        InputKind::TabletPad,
+        InputKind::Gesture,
+        InputKind::Gamepad,
+        InputKind::Switch,
+    ];
+
+    /// The kind of device a libinput event came from, `None` for device and other bookkeeping events.
+    pub fn of(event: &input::Event) -> Option<Self> {
+        match event {
+            input::Event::Keyboard(_) => Some(InputKind::Keyboard),
+            input::Event::Pointer(_) => Some(InputKind::Pointer),
+            input::Event::Touch(_) => Some(InputKind::Touch),
+            input::Event::Tablet(_) => Some(InputKind::Tablet),
+            input::Event::TabletPad(_) => Some(InputKind::TabletPad),
+            input::Event::Gesture(_) => Some(InputKind::Gesture),
+            input::Event::Switch(_) => Some(InputKind::Switch),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Events read from libinput, indexed like `InputKind::ALL`.
+    received: [AtomicU64; 8],
+    /// Motion events merged into a pending one because the channel to the client was full.
+    coalesced: AtomicU64,
+    /// Events thrown away because there was no server to send them to.
+    discarded: AtomicU64,
+    /// Events sent to each receiver by UID.
+    sent: Mutex<HashMap<String, u64>>,
+    frames: AtomicU64,
+    frame_time_total_us: AtomicU64,
+    frame_time_max_us: AtomicU64,
+}
+impl Metrics {
+    pub fn received(&self, kind: InputKind) {
+        let index = InputKind::ALL.iter().position(|k| *k == kind).unwrap();
+        self.received[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn coalesced(&self) {
+        self.coalesced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn discarded(&self, count: u64) {
+        self.discarded.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Count `events` sent to each of `receivers`.
+    pub fn sent<'a>(&self, receivers: impl IntoIterator<Item = &'a str>, events: u64) {
+        let mut sent = self.sent.lock().unwrap();
+        for uid in receivers {
+            *sent.entry(uid.to_string()).or_default() += events;
+        }
+    }
+
+    pub fn frame(&self, time: Duration) {
+        let time = time.as_micros() as u64;
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.frame_time_total_us.fetch_add(time, Ordering::Relaxed);
+        self.frame_time_max_us.fetch_max(time, Ordering::Relaxed);
+    }
+
+    /// Log everything counted since the last call and start counting from zero again.
+    fn log_and_reset(&self) {
+        let received = InputKind::ALL
+            .iter()
+            .zip(&self.received)
+            .map(|(kind, count)| (*kind, count.swap(0, Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+        let sent = std::mem::take(&mut *self.sent.lock().unwrap());
+        let frames = self.frames.swap(0, Ordering::Relaxed);
+        let frame_time_total = self.frame_time_total_us.swap(0, Ordering::Relaxed);
+        info!(
+            ?received,
+            coalesced = self.coalesced.swap(0, Ordering::Relaxed),
+            discarded = self.discarded.swap(0, Ordering::Relaxed),
+            ?sent,
+            frames,
+            frame_time_avg_us = frame_time_total.checked_div(frames).unwrap_or(0),
+            frame_time_max_us = self.frame_time_max_us.swap(0, Ordering::Relaxed),
+            "metrics"
+        );
+    }
+}
+
+/// Log the metrics every `interval` forever.
+pub async fn log_periodically(interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // the first tick completes immediately, before anything's been counted
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        METRICS.log_and_reset();
+    }
+}
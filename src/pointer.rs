@@ -0,0 +1,776 @@
+//! Keeping track of the pointer between events: merging motion, the cursor's position, dead zones,
+//! button repeat, and counting clicks and drags.
+
+use crate::config::{ButtonRepeatConfig, ClickConfig, CursorConfig, CursorEdges, DragConfig};
+use crate::events::{DragPhase, MouseButton, MouseInput};
+use crate::metrics::METRICS;
+use crate::seat::SeatSender;
+use glam::{vec2, Vec2};
+use input::Device;
+use stardust_xr_molecules::mouse::MouseEvent;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::warn;
+
+/// Pointer motion that can be merged with later motion of the same kind.
+///
+/// Relative and absolute motion are never merged into each other, a switch between the two always
+/// splits the run so the absolute position lands exactly where the device put it.
+#[derive(Debug, Clone, Copy)]
+pub enum Motion {
+    Relative(Vec2),
+    /// Later positions simply replace earlier ones.
+    Absolute {
+        position: Vec2,
+        /// Missing from events recorded before it was sent.
+        normalized: Option<Vec2>,
+    },
+}
+
+impl Motion {
+    /// Get the motion out of an event that carries nothing else.
+    fn from_event(event: &MouseInput) -> Option<Self> {
+        let MouseInput {
+            event:
+                MouseEvent {
+                    delta,
+                    scroll_distance: None,
+                    scroll_steps: None,
+                    buttons_up: None,
+                    buttons_down: None,
+                    ..
+                },
+            position,
+            position_normalized,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        match (delta, position) {
+            (Some(delta), None) => Some(Motion::Relative((*delta).into())),
+            (None, Some(position)) => Some(Motion::Absolute {
+                position: (*position).into(),
+                normalized: position_normalized.map(Into::into),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Merge `next` into this motion, or hand it back if it's a different kind of motion.
+    fn merge(&mut self, next: Motion) -> Option<Motion> {
+        match (self, next) {
+            (Motion::Relative(delta), Motion::Relative(next)) => *delta += next,
+            (absolute @ Motion::Absolute { .. }, next @ Motion::Absolute { .. }) => {
+                *absolute = next
+            }
+            (_, next) => return Some(next),
+        }
+        None
+    }
+
+    fn into_event(self) -> MouseInput {
+        match self {
+            Motion::Relative(delta) => {
+                MouseEvent::new(Some(delta.into()), None, None, None, None).into()
+            }
+            Motion::Absolute {
+                position,
+                normalized,
+            } => MouseInput::absolute(position, normalized),
+        }
+    }
+}
+
+/// Sends mouse events from the libinput thread without losing any.
+///
+/// Motion is coalesced into a single pending event while the channel is full so fast movement doesn't
+/// add latency, everything else waits for space in the channel.
+pub struct MouseEventSender {
+    tx: SeatSender<MouseInput>,
+    /// Motion waiting for space in the channel and the device it's from.
+    pending: Option<(Motion, Option<u32>)>,
+}
+
+impl MouseEventSender {
+    pub fn new(tx: SeatSender<MouseInput>) -> Self {
+        MouseEventSender { tx, pending: None }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn send_motion(&mut self, motion: Motion) {
+        let device = self.tx.device();
+        let motion = match self.pending.take() {
+            // only motion from the same device is merged, so every event is from a single one
+            Some((mut pending, pending_device)) if pending_device == device => {
+                match pending.merge(motion) {
+                    Some(next) => {
+                        let _ = self.tx.blocking_send_from(device, pending.into_event());
+                        next
+                    }
+                    None => pending,
+                }
+            }
+            Some((pending, pending_device)) => {
+                let _ = self
+                    .tx
+                    .blocking_send_from(pending_device, pending.into_event());
+                motion
+            }
+            None => motion,
+        };
+        self.try_send_motion(motion, device);
+    }
+
+    /// Send `motion` if there's space in the channel, otherwise keep it pending.
+    fn try_send_motion(&mut self, motion: Motion, device: Option<u32>) {
+        if let Err(TrySendError::Full(_)) = self.tx.try_send_from(device, motion.into_event()) {
+            METRICS.coalesced();
+            self.pending = Some((motion, device));
+        }
+    }
+
+    /// Release `buttons` if any of them are held.
+    pub fn release_buttons(&mut self, buttons: HashSet<u32>) {
+        if !buttons.is_empty() {
+            self.send(MouseEvent::new(
+                None,
+                None,
+                None,
+                Some(buttons.into_iter().collect()),
+                None,
+            ));
+        }
+    }
+
+    /// Retry sending the pending motion, if there is any.
+    pub fn flush_pending(&mut self) {
+        if let Some((motion, device)) = self.pending.take() {
+            self.try_send_motion(motion, device);
+        }
+    }
+
+    pub fn send(&mut self, event: impl Into<MouseInput>) {
+        // the pending motion happened before this event so it has to arrive first
+        if let Some((motion, device)) = self.pending.take() {
+            let _ = self.tx.blocking_send_from(device, motion.into_event());
+        }
+        let _ = self.tx.blocking_send(event.into());
+    }
+}
+
+/// Clicks the configured mouse buttons again and again while they're held, after a delay.
+pub struct ButtonRepeat {
+    config: ButtonRepeatConfig,
+    /// Evdev codes of the buttons that repeat, after remapping.
+    buttons: HashSet<u32>,
+    /// The held button that's repeating and when it next repeats, like keys only the last one
+    /// pressed repeats.
+    repeating: Option<(u32, Instant)>,
+}
+
+impl ButtonRepeat {
+    pub fn new(config: &ButtonRepeatConfig) -> Self {
+        let buttons = config
+            .buttons
+            .iter()
+            .filter_map(|button| {
+                let code = MouseButton::parse_code(button);
+                if code.is_none() {
+                    warn!(
+                        button,
+                        "unknown mouse button in button_repeat, it won't repeat"
+                    );
+                }
+                code
+            })
+            .collect();
+        ButtonRepeat {
+            config: config.clone(),
+            buttons,
+            repeating: None,
+        }
+    }
+
+    pub fn pressed(&mut self, button: u32) {
+        if self.config.rate > 0.0 && self.buttons.contains(&button) {
+            let next = Instant::now() + Duration::from_millis(self.config.delay);
+            self.repeating = Some((button, next));
+        }
+    }
+
+    pub fn released(&mut self, button: u32) {
+        if self
+            .repeating
+            .is_some_and(|(repeating, _)| repeating == button)
+        {
+            self.repeating = None;
+        }
+    }
+
+    /// When the held button will next repeat, if one is being held.
+    pub fn next_repeat(&self) -> Option<Instant> {
+        self.repeating.map(|(_, next)| next)
+    }
+
+    /// The button to click again if it's due and still among the `held` ones, which buttons are
+    /// taken out of without a release when they're let go of all at once.
+    pub fn repeat(&mut self, now: Instant, held: &HashMap<Device, HashSet<u32>>) -> Option<u32> {
+        let (button, next) = self.repeating.as_mut()?;
+        if !held.values().any(|held| held.contains(button)) {
+            self.repeating = None;
+            return None;
+        }
+        if *next > now {
+            return None;
+        }
+        // don't try to catch up on missed repeats and then send them all in a burst
+        *next = now + Duration::from_secs_f32(1.0 / self.config.rate);
+        Some(*button)
+    }
+}
+
+/// How long motion held back by the dead zone is kept for, so twitches far apart don't add up.
+const DEAD_ZONE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Holds back tiny relative motion until it adds up to something, jitter going back and forth
+/// mostly cancelling out on the way.
+pub struct DeadZone {
+    threshold: f32,
+    /// Motion held back so far and when it last came.
+    pending: Vec2,
+    last_motion: Option<Instant>,
+}
+
+impl DeadZone {
+    pub fn new(threshold: f32) -> Self {
+        DeadZone {
+            threshold,
+            pending: Vec2::ZERO,
+            last_motion: None,
+        }
+    }
+
+    /// The motion to send for `delta` together with what was held back, `None` while it's too short.
+    pub fn apply(&mut self, delta: Vec2, now: Instant) -> Option<Vec2> {
+        if self.threshold <= 0.0 {
+            return Some(delta);
+        }
+        let stale = self
+            .last_motion
+            .is_some_and(|last| now.duration_since(last) > DEAD_ZONE_TIMEOUT);
+        if stale {
+            self.pending = Vec2::ZERO;
+        }
+        self.last_motion = Some(now);
+        self.pending += delta;
+        (self.pending.length() >= self.threshold).then(|| std::mem::take(&mut self.pending))
+    }
+}
+
+/// Merges runs of consecutive motion-only mouse events so each run is sent as a single event.
+#[derive(Debug, Default)]
+pub struct MotionAccumulator {
+    /// The merged motion and the latest event merged into it, which says when and where it's from.
+    pending: Option<(Motion, MouseInput)>,
+}
+
+impl MotionAccumulator {
+    /// Feed in the next event, pushing any events that are ready to be sent onto `events`.
+    pub fn accumulate(&mut self, event: MouseInput, events: &mut Vec<MouseInput>) {
+        let Some(motion) = Motion::from_event(&event) else {
+            self.flush(events);
+            events.push(event);
+            return;
+        };
+        // motion from different seats or devices is different pointers moving
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|(_, last)| last.seat != event.seat || last.device != event.device)
+        {
+            self.flush(events);
+        }
+        match &mut self.pending {
+            Some((pending, last)) => {
+                if let Some(next) = pending.merge(motion) {
+                    events.push(Self::merged_event(*pending, last));
+                    *pending = next;
+                }
+                *last = event;
+            }
+            None => self.pending = Some((motion, event)),
+        }
+    }
+
+    /// Push the motion accumulated so far onto `events` as a single event.
+    pub fn flush(&mut self, events: &mut Vec<MouseInput>) {
+        if let Some((motion, last)) = self.pending.take() {
+            events.push(Self::merged_event(motion, &last));
+        }
+    }
+
+    /// The event for merged motion, from where and when `last` merged into it was.
+    fn merged_event(motion: Motion, last: &MouseInput) -> MouseInput {
+        MouseInput {
+            time: last.time,
+            seat: last.seat.clone(),
+            device: last.device,
+            ..motion.into_event()
+        }
+    }
+}
+
+/// The pointer position of every seat, which relative motion adds up into.
+pub struct Cursor {
+    bounds: Vec2,
+    edges: CursorEdges,
+    pub reset_on_focus: bool,
+    /// Each seat's cursor, which starts out in the middle.
+    positions: HashMap<String, Vec2>,
+}
+
+impl Cursor {
+    pub fn new(config: &CursorConfig) -> Self {
+        Cursor {
+            bounds: Self::bounds(config),
+            edges: Self::edges(config),
+            reset_on_focus: config.reset_on_focus,
+            positions: HashMap::new(),
+        }
+    }
+
+    fn bounds(config: &CursorConfig) -> Vec2 {
+        let [width, height] = config.bounds;
+        vec2(width as f32, height as f32)
+    }
+
+    /// The edges from `config`, clamping instead of wrapping around bounds that are 0 wide or high,
+    /// where there's nothing to wrap around and the position would turn into NaN.
+    fn edges(config: &CursorConfig) -> CursorEdges {
+        if config.edges == CursorEdges::Wrap && config.bounds.contains(&0) {
+            warn!(
+                bounds = ?config.bounds,
+                "the cursor can't wrap around empty bounds, clamping it instead"
+            );
+            return CursorEdges::Clamp;
+        }
+        config.edges
+    }
+
+    /// Move the cursor of the seat `event` is from by its motion, returning where it is now.
+    pub fn moved(&mut self, event: &MouseInput) -> Vec2 {
+        let position = self
+            .positions
+            .entry(event.seat.clone())
+            .or_insert(self.bounds / 2.0);
+        if let Some(absolute) = event.position {
+            *position = absolute.into();
+        }
+        if let Some(delta) = event.event.delta {
+            *position += Vec2::from(delta);
+        }
+        *position = Self::at_edges(*position, self.bounds, self.edges);
+        *position
+    }
+
+    /// Bring `position` back within `bounds` the way `edges` says to.
+    fn at_edges(position: Vec2, bounds: Vec2, edges: CursorEdges) -> Vec2 {
+        match edges {
+            CursorEdges::Clamp => position.clamp(Vec2::ZERO, bounds),
+            CursorEdges::Wrap => vec2(
+                position.x.rem_euclid(bounds.x),
+                position.y.rem_euclid(bounds.y),
+            ),
+            CursorEdges::Infinite => position,
+        }
+    }
+
+    /// Move the cursor of `seat`, or every cursor if it's `None`, straight to `position`.
+    pub fn warp(&mut self, position: Vec2, seat: Option<&str>) {
+        let position = Self::at_edges(position, self.bounds, self.edges);
+        match seat {
+            Some(seat) => {
+                self.positions.insert(seat.to_string(), position);
+            }
+            None => self.positions.values_mut().for_each(|p| *p = position),
+        }
+    }
+
+    /// Put every cursor back in the middle.
+    pub fn recenter(&mut self) {
+        self.positions.clear();
+    }
+
+    pub fn reconfigure(&mut self, config: &CursorConfig) {
+        self.bounds = Self::bounds(config);
+        self.edges = Self::edges(config);
+        self.reset_on_focus = config.reset_on_focus;
+        for position in self.positions.values_mut() {
+            *position = Self::at_edges(*position, self.bounds, self.edges);
+        }
+    }
+}
+
+/// Counts presses of the same button following quickly on each other without the pointer moving
+/// away in between.
+pub struct ClickCounter {
+    pub config: ClickConfig,
+    last_press: Option<LastPress>,
+    /// Where absolute motion last put the pointer, to tell how far the next moves it.
+    last_position: Option<Vec2>,
+}
+
+struct LastPress {
+    seat: String,
+    button: u32,
+    time: u64,
+    count: u32,
+    /// How far the pointer has moved since.
+    moved: f32,
+}
+
+impl ClickCounter {
+    pub fn new(config: ClickConfig) -> Self {
+        ClickCounter {
+            config,
+            last_press: None,
+            last_position: None,
+        }
+    }
+
+    /// Follow the pointer through `event`, returning the click count if it presses a button.
+    pub fn count(&mut self, event: &MouseInput) -> Option<u32> {
+        let moved = distance_moved(event, &mut self.last_position);
+        if let Some(last) = &mut self.last_press {
+            last.moved += moved;
+        }
+
+        let button = *event.event.buttons_down.as_ref()?.first()?;
+        let count = match &self.last_press {
+            Some(last)
+                if last.seat == event.seat
+                    && last.button == button
+                    && event.time.saturating_sub(last.time) <= self.config.interval * 1000
+                    && last.moved <= self.config.distance =>
+            {
+                last.count + 1
+            }
+            _ => 1,
+        };
+        self.last_press = Some(LastPress {
+            seat: event.seat.clone(),
+            button,
+            time: event.time,
+            count,
+            moved: 0.0,
+        });
+        Some(count)
+    }
+}
+
+/// Notices when the pointer moves far enough with a button held for it to be a drag.
+pub struct DragTracker {
+    pub config: DragConfig,
+    press: Option<Press>,
+    /// Where absolute motion last put the pointer, to tell how far the next moves it.
+    last_position: Option<Vec2>,
+}
+
+/// The button held down that might be dragging.
+struct Press {
+    seat: String,
+    button: u32,
+    moved: f32,
+    dragging: bool,
+}
+
+impl DragTracker {
+    pub fn new(config: DragConfig) -> Self {
+        DragTracker {
+            config,
+            press: None,
+            last_position: None,
+        }
+    }
+
+    /// Follow the pointer through `event`, returning whether a drag started or ended in it.
+    pub fn track(&mut self, event: &MouseInput) -> Option<DragPhase> {
+        let moved = distance_moved(event, &mut self.last_position);
+        let released = |press: &Press| {
+            press.seat == event.seat
+                && event
+                    .event
+                    .buttons_up
+                    .as_ref()
+                    .is_some_and(|buttons| buttons.contains(&press.button))
+        };
+        match &mut self.press {
+            Some(press) if released(press) => {
+                let dragging = press.dragging;
+                self.press = None;
+                dragging.then_some(DragPhase::Ended)
+            }
+            Some(press) if press.seat == event.seat && !press.dragging => {
+                press.moved += moved;
+                press.dragging = press.moved > self.config.threshold;
+                press.dragging.then_some(DragPhase::Started)
+            }
+            Some(_) => None,
+            None => {
+                let button = *event.event.buttons_down.as_ref()?.first()?;
+                self.press = Some(Press {
+                    seat: event.seat.clone(),
+                    button,
+                    moved: 0.0,
+                    dragging: false,
+                });
+                None
+            }
+        }
+    }
+}
+
+/// How far `event` moves the pointer, with where absolute motion last put it in `last_position`.
+fn distance_moved(event: &MouseInput, last_position: &mut Option<Vec2>) -> f32 {
+    let mut moved = event
+        .event
+        .delta
+        .map_or(0.0, |delta| Vec2::from(delta).length());
+    if let Some(position) = event.position.map(Vec2::from) {
+        if let Some(last) = last_position.replace(position) {
+            moved += position.distance(last);
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative(x: f32, y: f32) -> MouseInput {
+        MouseEvent::new(Some(vec2(x, y).into()), None, None, None, None).into()
+    }
+
+    fn press(button: u32) -> MouseInput {
+        MouseEvent::new(None, None, None, None, Some(vec![button])).into()
+    }
+
+    fn delta(event: &MouseInput) -> Option<Vec2> {
+        event.event.delta.map(Vec2::from)
+    }
+
+    #[test]
+    fn motion_is_merged_until_something_else_comes() {
+        let mut accumulator = MotionAccumulator::default();
+        let mut events = Vec::new();
+        accumulator.accumulate(relative(1.0, 2.0), &mut events);
+        accumulator.accumulate(relative(0.5, -1.0), &mut events);
+        assert!(events.is_empty());
+        let left = input_event_codes::BTN_LEFT!();
+        accumulator.accumulate(press(left), &mut events);
+        assert_eq!(events.len(), 2);
+        assert_eq!(delta(&events[0]), Some(vec2(1.5, 1.0)));
+        assert_eq!(events[1].event.buttons_down, Some(vec![left]));
+        // nothing is left over once something else has been sent
+        accumulator.flush(&mut events);
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn merged_motion_is_from_the_latest_event() {
+        let mut accumulator = MotionAccumulator::default();
+        let mut events = Vec::new();
+        accumulator.accumulate(
+            MouseInput {
+                time: 1,
+                ..relative(1.0, 0.0)
+            },
+            &mut events,
+        );
+        accumulator.accumulate(
+            MouseInput {
+                time: 2,
+                ..relative(1.0, 0.0)
+            },
+            &mut events,
+        );
+        accumulator.flush(&mut events);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].time, 2);
+    }
+
+    #[test]
+    fn absolute_motion_after_relative_motion_is_carried_on() {
+        let mut accumulator = MotionAccumulator::default();
+        let mut events = Vec::new();
+        accumulator.accumulate(relative(1.0, 0.0), &mut events);
+        accumulator.accumulate(
+            MouseInput::absolute(vec2(10.0, 20.0), None::<Vec2>),
+            &mut events,
+        );
+        accumulator.accumulate(
+            MouseInput::absolute(vec2(30.0, 40.0), None::<Vec2>),
+            &mut events,
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(delta(&events[0]), Some(vec2(1.0, 0.0)));
+        accumulator.flush(&mut events);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].position.map(Vec2::from), Some(vec2(30.0, 40.0)));
+    }
+
+    #[test]
+    fn motion_from_different_devices_isnt_merged() {
+        let mut accumulator = MotionAccumulator::default();
+        let mut events = Vec::new();
+        let from = |device| MouseInput {
+            device: Some(device),
+            ..relative(1.0, 0.0)
+        };
+        accumulator.accumulate(from(1), &mut events);
+        accumulator.accumulate(from(2), &mut events);
+        accumulator.flush(&mut events);
+        let devices = events.iter().map(|event| event.device).collect::<Vec<_>>();
+        assert_eq!(devices, [Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn dead_zone_lets_motion_through_once_it_reaches_the_threshold() {
+        let mut dead_zone = DeadZone::new(2.0);
+        let now = Instant::now();
+        assert_eq!(dead_zone.apply(vec2(1.0, 0.0), now), None);
+        assert_eq!(dead_zone.apply(vec2(1.0, 0.0), now), Some(vec2(2.0, 0.0)));
+        // what got through doesn't count towards the next motion
+        assert_eq!(dead_zone.apply(vec2(1.5, 0.0), now), None);
+    }
+
+    #[test]
+    fn jitter_in_the_dead_zone_cancels_out() {
+        let mut dead_zone = DeadZone::new(2.0);
+        let now = Instant::now();
+        assert_eq!(dead_zone.apply(vec2(1.5, 0.0), now), None);
+        assert_eq!(dead_zone.apply(vec2(-1.5, 0.0), now), None);
+        assert_eq!(dead_zone.apply(vec2(1.5, 0.0), now), None);
+    }
+
+    #[test]
+    fn dead_zone_forgets_motion_older_than_its_timeout() {
+        let mut dead_zone = DeadZone::new(2.0);
+        let start = Instant::now();
+        assert_eq!(dead_zone.apply(vec2(1.5, 0.0), start), None);
+        let later = start + DEAD_ZONE_TIMEOUT;
+        assert_eq!(dead_zone.apply(vec2(0.5, 0.0), later), Some(vec2(2.0, 0.0)));
+        assert_eq!(dead_zone.apply(vec2(1.5, 0.0), later), None);
+        let stale = later + DEAD_ZONE_TIMEOUT + Duration::from_millis(1);
+        assert_eq!(dead_zone.apply(vec2(0.5, 0.0), stale), None);
+    }
+
+    #[test]
+    fn no_dead_zone_lets_everything_through() {
+        let mut dead_zone = DeadZone::new(0.0);
+        assert_eq!(
+            dead_zone.apply(Vec2::ZERO, Instant::now()),
+            Some(Vec2::ZERO)
+        );
+    }
+
+    fn press_at(button: u32, time: u64) -> MouseInput {
+        MouseInput {
+            time,
+            ..press(button)
+        }
+    }
+
+    fn release(button: u32) -> MouseInput {
+        MouseEvent::new(None, None, None, Some(vec![button]), None).into()
+    }
+
+    #[test]
+    fn presses_within_the_interval_count_up() {
+        let mut clicks = ClickCounter::new(ClickConfig::default());
+        let interval = clicks.config.interval * 1000;
+        let left = input_event_codes::BTN_LEFT!();
+        assert_eq!(clicks.count(&press_at(left, 0)), Some(1));
+        assert_eq!(clicks.count(&release(left)), None);
+        assert_eq!(clicks.count(&press_at(left, interval)), Some(2));
+        // the interval is from the last press, not the first
+        assert_eq!(clicks.count(&press_at(left, interval * 2)), Some(3));
+        assert_eq!(clicks.count(&press_at(left, interval * 3 + 1)), Some(1));
+    }
+
+    #[test]
+    fn moving_away_or_pressing_another_button_starts_over() {
+        let mut clicks = ClickCounter::new(ClickConfig::default());
+        let distance = clicks.config.distance;
+        let (left, right) = (
+            input_event_codes::BTN_LEFT!(),
+            input_event_codes::BTN_RIGHT!(),
+        );
+        assert_eq!(clicks.count(&press_at(left, 0)), Some(1));
+        assert_eq!(clicks.count(&relative(distance, 0.0)), None);
+        assert_eq!(clicks.count(&press_at(left, 1000)), Some(2));
+        assert_eq!(clicks.count(&relative(distance / 2.0, 0.0)), None);
+        assert_eq!(clicks.count(&relative(distance, 0.0)), None);
+        assert_eq!(clicks.count(&press_at(left, 2000)), Some(1));
+        assert_eq!(clicks.count(&press_at(right, 3000)), Some(1));
+    }
+
+    #[test]
+    fn clicks_on_different_seats_are_counted_apart() {
+        let mut clicks = ClickCounter::new(ClickConfig::default());
+        let left = input_event_codes::BTN_LEFT!();
+        assert_eq!(clicks.count(&press_at(left, 0)), Some(1));
+        let other_seat = MouseInput {
+            seat: "seat1".to_string(),
+            ..press_at(left, 1000)
+        };
+        assert_eq!(clicks.count(&other_seat), Some(1));
+    }
+
+    #[test]
+    fn moving_past_the_threshold_with_a_button_held_drags() {
+        let mut drags = DragTracker::new(DragConfig::default());
+        let threshold = drags.config.threshold;
+        let left = input_event_codes::BTN_LEFT!();
+        assert_eq!(drags.track(&press(left)), None);
+        assert_eq!(drags.track(&relative(threshold, 0.0)), None);
+        assert_eq!(drags.track(&relative(0.1, 0.0)), Some(DragPhase::Started));
+        assert_eq!(drags.track(&relative(threshold, 0.0)), None);
+        assert_eq!(drags.track(&release(left)), Some(DragPhase::Ended));
+        assert_eq!(drags.track(&relative(threshold * 2.0, 0.0)), None);
+    }
+
+    #[test]
+    fn absolute_motion_counts_towards_a_drag() {
+        let mut drags = DragTracker::new(DragConfig::default());
+        let threshold = drags.config.threshold;
+        let left = input_event_codes::BTN_LEFT!();
+        let at = |x| MouseInput::absolute(vec2(x, 0.0), None::<Vec2>);
+        assert_eq!(drags.track(&at(100.0)), None);
+        assert_eq!(drags.track(&press(left)), None);
+        assert_eq!(
+            drags.track(&at(100.0 + threshold * 2.0)),
+            Some(DragPhase::Started)
+        );
+    }
+
+    #[test]
+    fn releasing_without_moving_is_no_drag() {
+        let mut drags = DragTracker::new(DragConfig::default());
+        let (left, right) = (
+            input_event_codes::BTN_LEFT!(),
+            input_event_codes::BTN_RIGHT!(),
+        );
+        assert_eq!(drags.track(&press(left)), None);
+        // only releasing the button that was pressed first ends the press
+        assert_eq!(drags.track(&release(right)), None);
+        assert_eq!(drags.track(&release(left)), None);
+        assert!(drags.press.is_none());
+    }
+}
@@ -0,0 +1,147 @@
+//! Writing the mouse and keyboard input Eclipse receives to a file, to reproduce problems with,
+//! and playing it back without any devices.
+//!
+//! Recordings are JSON lines, one event per line along with when it was received.
+
+use crate::events::{timestamp, KeyboardInput, MouseInput};
+use crate::idle;
+use crate::metrics::{InputKind, METRICS};
+use color_eyre::eyre::{Result, WrapErr};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Sender;
+use tracing::info;
+
+/// One line of a recording.
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    /// When Eclipse received the event, in microseconds on the monotonic clock like `timestamp`.
+    time: u64,
+    #[serde(flatten)]
+    event: RecordedEvent<'a>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "event", rename_all = "snake_case")]
+pub enum RecordedEvent<'a> {
+    Mouse(&'a MouseInput),
+    Keyboard(&'a KeyboardInput),
+}
+
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+impl Recorder {
+    /// Start a new recording at `path`, replacing whatever was there.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Couldn't create recording {}", path.display()))?;
+        Ok(Recorder {
+            file: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, event: RecordedEvent) -> std::io::Result<()> {
+        let record = Record {
+            time: timestamp(),
+            event,
+        };
+        serde_json::to_writer(&mut self.file, &record)?;
+        self.file.write_all(b"\n")
+    }
+
+    /// Write out what's been recorded so far, so it isn't lost if Eclipse doesn't exit cleanly.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// A line of a recording read back in.
+#[derive(Debug, Deserialize)]
+pub struct Recorded {
+    time: u64,
+    #[serde(flatten)]
+    event: ReplayedEvent,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", content = "event", rename_all = "snake_case")]
+enum ReplayedEvent {
+    Mouse(MouseInput),
+    Keyboard(KeyboardInput),
+}
+
+/// Read the whole recording at `path`.
+pub fn load(path: &Path) -> Result<Vec<Recorded>> {
+    let recording = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Couldn't read recording {}", path.display()))?;
+    recording
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).wrap_err_with(|| {
+                format!(
+                    "Couldn't parse line {} of recording {}",
+                    index + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Send the recorded events through `mouse_tx` and `keyboard_tx` as far apart as they were
+/// received, `speed` times as fast, until they run out or the other end of `stop` is closed.
+///
+/// The events keep the times they were recorded with, so they're ordered the same way as before.
+pub fn replay(
+    records: Vec<Recorded>,
+    speed: f64,
+    mouse_tx: Option<&Sender<MouseInput>>,
+    keyboard_tx: Option<&Sender<KeyboardInput>>,
+    stop: &UnixStream,
+) {
+    let Some(first) = records.first().map(|record| record.time) else {
+        return;
+    };
+    info!(events = records.len(), speed, "replaying recording");
+    let start = Instant::now();
+    let mut stop_pollfd = [PollFd::new(stop.as_raw_fd(), PollFlags::POLLIN)];
+    for record in records {
+        let due = start + Duration::from_micros(record.time.saturating_sub(first)).div_f64(speed);
+        // waiting on `stop` so stopping doesn't have to wait for the next event
+        loop {
+            let timeout = due.saturating_duration_since(Instant::now());
+            match poll(&mut stop_pollfd, crate::poll_timeout(Some(timeout))) {
+                Ok(0) => break,
+                Err(Errno::EINTR) => continue,
+                _ => return,
+            }
+        }
+        idle::input_happened();
+        match record.event {
+            // recorded input that isn't forwarded is skipped like it is from devices
+            ReplayedEvent::Mouse(event) => {
+                if let Some(mouse_tx) = mouse_tx {
+                    METRICS.received(InputKind::Pointer);
+                    let _ = mouse_tx.blocking_send(event);
+                }
+            }
+            ReplayedEvent::Keyboard(event) => {
+                if let Some(keyboard_tx) = keyboard_tx {
+                    METRICS.received(InputKind::Keyboard);
+                    let _ = keyboard_tx.blocking_send(event);
+                }
+            }
+        }
+    }
+    info!("replay finished");
+}
@@ -0,0 +1,269 @@
+//! Which receivers each kind of input is sent to, and the pulse senders sending it.
+
+use crate::config::BroadcastMode;
+use crate::events::{CursorWarp, EventBatch, PulseEvent};
+use crate::metrics::METRICS;
+use crate::{events, try_recv};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use stardust_xr_fusion::core::schemas::flex::flexbuffers;
+use stardust_xr_fusion::core::values::Transform;
+use stardust_xr_fusion::data::{
+    NewReceiverInfo, PulseReceiver, PulseReceiverHandler, PulseSender, PulseSenderHandler,
+};
+use stardust_xr_fusion::fields::UnknownField;
+use stardust_xr_fusion::node::{NodeError, NodeType};
+use stardust_xr_fusion::spatial::Spatial;
+use stardust_xr_fusion::HandlerWrapper;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::time::SystemTime;
+use tokio::sync::mpsc::Receiver;
+use tracing::{debug, info, warn};
+
+/// Events waiting to be sent together at the end of the frame, in runs going to the same receivers.
+pub struct Batch<E> {
+    /// The keys and values of the mask of the sender the batches go through.
+    mask: BTreeMap<String, String>,
+    runs: Vec<(Vec<String>, Vec<E>)>,
+}
+
+impl<E: Serialize> Batch<E> {
+    pub fn new(mask: &[u8]) -> Self {
+        Batch {
+            mask: events::mask_fields(mask),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, targets: &[(&str, &PulseReceiver)], event: E) {
+        let same_targets = |uids: &Vec<String>| {
+            uids.iter()
+                .map(String::as_str)
+                .eq(targets.iter().map(|(uid, _)| *uid))
+        };
+        match self.runs.last_mut() {
+            Some((uids, events)) if same_targets(uids) => events.push(event),
+            _ => self.runs.push((
+                targets.iter().map(|(uid, _)| uid.to_string()).collect(),
+                vec![event],
+            )),
+        }
+    }
+
+    /// Send each run to its receivers that are still connected as one pulse.
+    pub fn send(&mut self, sender: &PulseSender) {
+        let receivers = sender.receivers();
+        for (uids, events) in self.runs.drain(..) {
+            let targets = uids
+                .iter()
+                .filter_map(|uid| Some((uid.as_str(), &receivers.get(uid)?.0)))
+                .collect::<Vec<_>>();
+            METRICS.sent(targets.iter().map(|(uid, _)| *uid), events.len() as u64);
+            let receivers = targets
+                .iter()
+                .map(|(_, receiver)| *receiver)
+                .collect::<Vec<_>>();
+            EventBatch {
+                mask: &self.mask,
+                events: &events,
+            }
+            .send_event(sender, &receivers);
+        }
+    }
+}
+
+/// Pick the receivers an event goes to: all of them when broadcasting, otherwise the `focused`
+/// receiver if it's connected to the sender or else the one `fallback` picks.
+///
+/// `receivers` has to come from the sender the event is sent through, since the server only lists
+/// receivers whose mask matches the sender's. That keeps a focus picked for one kind of input from
+/// sending it to a receiver that never asked for it.
+pub fn select_receivers<'a, S: BuildHasher>(
+    broadcast_mode: BroadcastMode,
+    receivers: &'a HashMap<String, (PulseReceiver, UnknownField), S>,
+    focused: Option<&str>,
+    fallback: impl FnOnce() -> Option<String>,
+) -> Vec<(&'a str, &'a PulseReceiver)> {
+    let target = |(uid, (receiver, _)): (&'a String, &'a (PulseReceiver, UnknownField))| {
+        (uid.as_str(), receiver)
+    };
+    match broadcast_mode {
+        BroadcastMode::On => receivers.iter().map(target).collect(),
+        BroadcastMode::Off => focused
+            .and_then(|uid| receivers.get_key_value(uid))
+            .or_else(|| receivers.get_key_value(&fallback()?))
+            .map(target)
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Send `event` to every receiver of `sender`, if the feature it's for is on.
+pub fn send_to_all<E: PulseEvent>(
+    sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    event: &E,
+) {
+    let Some(sender) = sender else {
+        return;
+    };
+    let receivers = sender.node().receivers();
+    let targets = select_receivers(BroadcastMode::On, &receivers, None, || None);
+    send_to(event, sender.node(), &targets)
+}
+
+/// Send `event` to the `targets` picked by `select_receivers`.
+pub fn send_to<E: PulseEvent>(event: &E, sender: &PulseSender, targets: &[(&str, &PulseReceiver)]) {
+    METRICS.sent(targets.iter().map(|(uid, _)| *uid), 1);
+    let receivers = targets
+        .iter()
+        .map(|(_, receiver)| *receiver)
+        .collect::<Vec<_>>();
+    event.send_event(sender, &receivers)
+}
+
+/// Send everything queued up in `events` through `sender`, if this kind of input is forwarded.
+pub fn forward_all<E: PulseEvent>(
+    sender: &Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    events: &mut Option<Receiver<E>>,
+    broadcast_mode: BroadcastMode,
+    preferred: Option<&str>,
+) {
+    let Some(sender) = sender else {
+        return;
+    };
+    let receivers = sender.node().receivers();
+    while let Some(event) = try_recv(events) {
+        let targets = select_receivers(broadcast_mode, &receivers, None, || {
+            closest_receiver(sender, preferred)
+        });
+        send_to(&event, sender.node(), &targets)
+    }
+}
+
+/// Create a pulse sender sending input of `kind` with `mask`.
+pub fn create_pulse_sender(
+    root: &Spatial,
+    mask: &[u8],
+    kind: &'static str,
+) -> Result<HandlerWrapper<PulseSender, ReceiverHandler>, NodeError> {
+    PulseSender::create(root, Transform::identity(), mask)?.wrap(ReceiverHandler::new(kind))
+}
+
+/// Create `sender` if it's `wanted` and doesn't exist yet, or drop it if it's not, after a reload.
+pub fn update_pulse_sender(
+    root: &Spatial,
+    sender: &mut Option<HandlerWrapper<PulseSender, ReceiverHandler>>,
+    wanted: bool,
+    mask: &[u8],
+    kind: &'static str,
+) {
+    if !wanted {
+        *sender = None;
+    } else if sender.is_none() {
+        match create_pulse_sender(root, mask, kind) {
+            Ok(created) => *sender = Some(created),
+            Err(err) => warn!(kind, ?err, "couldn't create pulse sender"),
+        }
+    }
+}
+
+/// The closest receiver of `sender`, or `preferred` if it's connected.
+pub fn closest_receiver(
+    sender: &HandlerWrapper<PulseSender, ReceiverHandler>,
+    preferred: Option<&str>,
+) -> Option<String> {
+    sender.lock_wrapped().closest(preferred)
+}
+
+/// Passes on the cursor warps receivers send along with who sent them.
+pub struct CursorWarpHandler {
+    pub tx: tokio::sync::mpsc::UnboundedSender<(String, CursorWarp)>,
+}
+
+impl PulseReceiverHandler for CursorWarpHandler {
+    fn data(&mut self, uid: &str, data: &[u8], _data_reader: flexbuffers::MapReader<&[u8]>) {
+        match flexbuffers::from_slice(data) {
+            Ok(warp) => {
+                let _ = self.tx.send((uid.to_string(), warp));
+            }
+            Err(err) => debug!(sender = uid, ?err, "invalid cursor warp"),
+        }
+    }
+}
+
+/// Keeps track of the receivers coming and going on one of the pulse senders.
+///
+/// This is only bookkeeping for listing receivers and picking the closest one. The server doesn't
+/// say which mask a receiver has, just that it matches the sender's, so there's none to store, and
+/// events are sent to the receivers in `receivers()` on the sender since only it has the handles to
+/// send them with.
+pub struct ReceiverHandler {
+    /// What the sender sends, which its mask matches, like `mouse`.
+    pub kind: &'static str,
+    pub receivers: BTreeMap<String, ReceiverEntry>,
+}
+
+/// A receiver connected to a pulse sender.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceiverEntry {
+    /// Path of the receiver's field.
+    pub field: Option<String>,
+    /// How far the field is from the sender, as of when it connected. The mouse sender's are
+    /// measured again every frame.
+    pub distance: f32,
+    /// Unix time in seconds it connected at.
+    pub connected_at: u64,
+}
+
+impl ReceiverHandler {
+    fn new(kind: &'static str) -> Self {
+        ReceiverHandler {
+            kind,
+            receivers: BTreeMap::new(),
+        }
+    }
+
+    /// The receiver with the shortest `distance`, or `preferred` if it's connected.
+    pub fn closest(&self, preferred: Option<&str>) -> Option<String> {
+        preferred
+            .filter(|uid| self.receivers.contains_key(*uid))
+            .map(str::to_string)
+            .or_else(|| {
+                self.receivers
+                    .iter()
+                    .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+                    .map(|(uid, _)| uid.clone())
+            })
+    }
+}
+
+impl PulseSenderHandler for ReceiverHandler {
+    fn new_receiver(
+        &mut self,
+        info: NewReceiverInfo,
+        _receiver: PulseReceiver,
+        field: UnknownField,
+    ) {
+        let entry = ReceiverEntry {
+            field: field.node().get_path().ok(),
+            distance: info.distance,
+            connected_at: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+        };
+        info!(
+            kind = self.kind,
+            uid = info.uid,
+            distance = entry.distance,
+            field = entry.field,
+            "receiver connected"
+        );
+        self.receivers.insert(info.uid, entry);
+    }
+
+    fn drop_receiver(&mut self, uid: &str) {
+        info!(kind = self.kind, uid, "receiver disconnected");
+        self.receivers.remove(uid);
+    }
+}
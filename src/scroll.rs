@@ -0,0 +1,475 @@
+//! Turning libinput's scroll events into the ones sent on: smooth scrolling, momentum, locking to
+//! an axis and scrolling bound to hotkeys.
+
+use crate::config::{MomentumConfig, ScrollConfig, ScrollKeysConfig};
+use crate::error::EclipseError;
+use crate::events::{MouseInput, ScrollSource};
+use crate::keyboard::{Hotkey, KeyboardState};
+use color_eyre::eyre::Result;
+use glam::{vec2, Vec2};
+use input::event::pointer::{Axis, PointerScrollEvent, PointerScrollWheelEvent};
+use input::event::{EventTrait, PointerEvent};
+use input::Device;
+use stardust_xr_molecules::mouse::MouseEvent;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Swap the scroll axes while `horizontal_modifier` is held, so a vertical wheel scrolls sideways.
+pub fn scroll_axes(
+    delta: Vec2,
+    horizontal_modifier: Option<&str>,
+    keyboard_state: &KeyboardState,
+) -> Vec2 {
+    match horizontal_modifier {
+        Some(modifier) if keyboard_state.modifier_active(modifier) => vec2(delta.y, delta.x),
+        _ => delta,
+    }
+}
+
+/// How far a scroll wheel event turned the wheel along `axis`, in 120ths of a detent.
+///
+/// Some older devices only give libinput the legacy wheel axes, which leaves the v120 value at zero,
+/// so this falls back on the scroll value with `legacy_step` of it making a detent.
+pub fn wheel_v120(event: &PointerScrollWheelEvent, axis: Axis, legacy_step: f32) -> f32 {
+    // libinput complains about reading axes the event doesn't have
+    if !event.has_axis(axis) {
+        return 0.0;
+    }
+    let v120 = event.scroll_value_v120(axis) as f32;
+    if v120 != 0.0 || legacy_step <= 0.0 {
+        return v120;
+    }
+    event.scroll_value(axis) as f32 / legacy_step * 120.0
+}
+
+/// The distance a touchpad or other continuous scroll event moved, as libinput reports it.
+fn scroll_distance(event: &impl PointerScrollEvent) -> Vec2 {
+    // libinput complains about reading axes the event doesn't have
+    let value = |axis| {
+        if event.has_axis(axis) {
+            event.scroll_value(axis) as f32
+        } else {
+            0.0
+        }
+    };
+    vec2(value(Axis::Horizontal), value(Axis::Vertical))
+}
+
+/// What handling a finger or continuous scroll event needs from it, the two are handled the same.
+pub struct SmoothScroll {
+    pub source: ScrollSource,
+    /// With the scroll settings applied.
+    pub distance: Vec2,
+    /// Whether the event ends the scroll.
+    pub stopped: bool,
+    pub device: Device,
+}
+
+impl SmoothScroll {
+    /// Read a finger or continuous scroll event, `None` for any other pointer event.
+    pub fn read(
+        event: &PointerEvent,
+        config: &ScrollConfig,
+        natural_scroll: &HashMap<Device, bool>,
+        horizontal_modifier: Option<&str>,
+        keyboard_state: &KeyboardState,
+    ) -> Option<Self> {
+        let (source, distance, stopped, device) = match event {
+            PointerEvent::ScrollContinuous(s) => (
+                ScrollSource::Continuous,
+                scroll_distance(s),
+                scroll_stopped(s),
+                s.device(),
+            ),
+            PointerEvent::ScrollFinger(s) => (
+                ScrollSource::Finger,
+                scroll_distance(s),
+                scroll_stopped(s),
+                s.device(),
+            ),
+            _ => return None,
+        };
+        let natural = natural_scroll.get(&device).copied().unwrap_or_default();
+        Some(SmoothScroll {
+            source,
+            distance: scroll_axes(
+                config.apply(distance, natural),
+                horizontal_modifier,
+                keyboard_state,
+            ),
+            stopped,
+            device,
+        })
+    }
+}
+
+/// How often scrolling that carries on by itself is sent.
+const MOMENTUM_INTERVAL: Duration = Duration::from_millis(8);
+
+/// Keeps finger and continuous scrolling going after it ends, slowing down until it stops.
+pub struct ScrollMomentum {
+    config: MomentumConfig,
+    /// The scroll distances within the last `window` and when they came, to measure the speed by.
+    recent: Vec<(Instant, Vec2)>,
+    coast: Option<Coast>,
+}
+
+/// Scrolling that's carrying on by itself.
+struct Coast {
+    /// In scroll units per second.
+    velocity: Vec2,
+    source: ScrollSource,
+    last_tick: Instant,
+}
+
+impl ScrollMomentum {
+    pub fn new(config: MomentumConfig) -> Self {
+        ScrollMomentum {
+            config,
+            recent: Vec::new(),
+            coast: None,
+        }
+    }
+
+    /// Follow a scroll event, returning the events to send for it: the end of a coast it interrupts
+    /// and then the event itself, unless it's the end of the scroll and that carries on instead.
+    pub fn scrolled(
+        &mut self,
+        distance: Vec2,
+        stopped: bool,
+        source: ScrollSource,
+    ) -> Vec<MouseInput> {
+        let scroll = continuous_scroll(distance, stopped, source);
+        if !self.config.enabled {
+            return vec![scroll];
+        }
+        let now = Instant::now();
+        let window = Duration::from_millis(self.config.window);
+        // the coast is ended like a cancelled one, it could even be from another source
+        let mut events = self
+            .coast
+            .take()
+            .map(|coast| continuous_scroll(Vec2::ZERO, true, coast.source))
+            .into_iter()
+            .collect::<Vec<_>>();
+        self.recent
+            .retain(|(time, _)| now.duration_since(*time) <= window);
+        self.recent.push((now, distance));
+        if !stopped {
+            events.push(scroll);
+            return events;
+        }
+        let distance = std::mem::take(&mut self.recent)
+            .into_iter()
+            .fold(Vec2::ZERO, |total, (_, distance)| total + distance);
+        let velocity = distance / window.as_secs_f32().max(0.001);
+        if velocity.length() < self.config.min_speed {
+            events.push(scroll);
+            return events;
+        }
+        self.coast = Some(Coast {
+            velocity,
+            source,
+            last_tick: now,
+        });
+        events
+    }
+
+    /// Stop the scrolling right where it is, returning the event ending it if it was carrying on.
+    #[must_use]
+    pub fn cancel(&mut self) -> Option<MouseInput> {
+        self.recent.clear();
+        let coast = self.coast.take()?;
+        Some(continuous_scroll(Vec2::ZERO, true, coast.source))
+    }
+
+    pub fn next_tick(&self) -> Option<Instant> {
+        self.coast
+            .as_ref()
+            .map(|coast| coast.last_tick + MOMENTUM_INTERVAL)
+    }
+
+    /// The scroll event to send now if it's time to, which stops the scroll once it has slowed down
+    /// enough to end.
+    pub fn tick(&mut self, now: Instant) -> Option<MouseInput> {
+        let coast = self.coast.as_mut()?;
+        let elapsed = now.saturating_duration_since(coast.last_tick);
+        if elapsed < MOMENTUM_INTERVAL {
+            return None;
+        }
+        let elapsed = elapsed.as_secs_f32();
+        coast.last_tick = now;
+        coast.velocity *= (-self.config.friction * elapsed).exp();
+        if coast.velocity.length() < self.config.min_speed {
+            let source = coast.source;
+            self.coast = None;
+            return Some(continuous_scroll(Vec2::ZERO, true, source));
+        }
+        Some(continuous_scroll(
+            coast.velocity * elapsed,
+            false,
+            coast.source,
+        ))
+    }
+}
+
+/// Whether a finger or continuous scroll event is the one libinput ends the scroll with, which has
+/// a value of 0 on the axes that stopped.
+fn scroll_stopped(event: &impl PointerScrollEvent) -> bool {
+    [Axis::Horizontal, Axis::Vertical]
+        .into_iter()
+        .any(|axis| event.has_axis(axis) && event.scroll_value(axis) == 0.0)
+}
+
+/// Keeps a finger or continuous scroll on the axis it mostly goes along once that's clear.
+pub struct AxisLock {
+    threshold: f32,
+    /// How far the scroll has gone along each axis since it started.
+    travelled: Vec2,
+    /// What the distances are multiplied by to keep only the locked axis, `None` until one is.
+    locked: Option<Vec2>,
+}
+
+impl AxisLock {
+    pub fn new(threshold: f32) -> Self {
+        AxisLock {
+            threshold,
+            travelled: Vec2::ZERO,
+            locked: None,
+        }
+    }
+
+    /// The part of `distance` that's kept, starting over for the next scroll once it's `stopped`.
+    pub fn apply(&mut self, distance: Vec2, stopped: bool) -> Vec2 {
+        if self.threshold <= 0.0 {
+            return distance;
+        }
+        if self.locked.is_none() {
+            self.travelled += distance.abs();
+            if self.travelled.max_element() >= self.threshold {
+                self.locked = Some(if self.travelled.x > self.travelled.y {
+                    Vec2::X
+                } else {
+                    Vec2::Y
+                });
+            }
+        }
+        let distance = distance * self.locked.unwrap_or(Vec2::ONE);
+        if stopped {
+            self.travelled = Vec2::ZERO;
+            self.locked = None;
+        }
+        distance
+    }
+}
+
+/// Turns scrolling into key presses in the directions that have keys set, adding up fractions of
+/// steps until they make a whole one.
+pub struct ScrollKeys {
+    /// The keys for each direction along x and then y, towards the negative end first, so left,
+    /// right, up and down. `None` where it stays scrolling.
+    keys: [[Option<Hotkey>; 2]; 2],
+    finger_distance: f32,
+    /// Steps scrolled along each axis that haven't made a key press yet.
+    accumulated: Vec2,
+}
+
+impl ScrollKeys {
+    pub fn load(config: &ScrollKeysConfig) -> Result<Self, EclipseError> {
+        let key = |keys: &str| {
+            (config.enabled && !keys.is_empty())
+                .then(|| Hotkey::parse(keys).map_err(|err| err.in_setting("scroll.keys")))
+                .transpose()
+        };
+        Ok(ScrollKeys {
+            keys: [
+                [key(&config.left)?, key(&config.right)?],
+                [key(&config.up)?, key(&config.down)?],
+            ],
+            finger_distance: config.finger_distance,
+            accumulated: Vec2::ZERO,
+        })
+    }
+
+    /// Take a wheel's scroll in v120 units, see `scrolled`.
+    pub fn wheel_scrolled(&mut self, v120: Vec2) -> (Vec<&Hotkey>, Option<Vec2>) {
+        self.scrolled(v120, 120.0)
+    }
+
+    /// Take a touchpad's or other continuous scroll's distance, see `scrolled`.
+    pub fn finger_scrolled(&mut self, distance: Vec2) -> (Vec<&Hotkey>, Option<Vec2>) {
+        self.scrolled(distance, self.finger_distance)
+    }
+
+    /// Take what's scrolled in directions that have keys, a key press for every `step` of it,
+    /// returning the keys to type and what there's left to scroll, `None` if the keys took all of it.
+    fn scrolled(&mut self, distance: Vec2, step: f32) -> (Vec<&Hotkey>, Option<Vec2>) {
+        if step <= 0.0 {
+            return (Vec::new(), Some(distance));
+        }
+        let steps = distance / step;
+        let mut taps = Vec::new();
+        let mut left = steps;
+        let mut took_all = true;
+        for axis in 0..2 {
+            let step = steps[axis];
+            if step == 0.0 {
+                continue;
+            }
+            let Some(hotkey) = &self.keys[axis][usize::from(step > 0.0)] else {
+                took_all = false;
+                continue;
+            };
+            left[axis] = 0.0;
+            // turning around starts over instead of undoing part of a step
+            if self.accumulated[axis] * step < 0.0 {
+                self.accumulated[axis] = 0.0;
+            }
+            self.accumulated[axis] += step;
+            let whole = self.accumulated[axis].trunc();
+            self.accumulated[axis] -= whole;
+            taps.extend(std::iter::repeat_n(hotkey, whole.abs() as usize));
+        }
+        (
+            taps,
+            (!took_all || steps == Vec2::ZERO).then_some(left * step),
+        )
+    }
+}
+
+fn continuous_scroll(distance: Vec2, stopped: bool, source: ScrollSource) -> MouseInput {
+    MouseInput {
+        scroll_stop: stopped,
+        ..MouseInput::scroll(
+            MouseEvent::new(None, Some(distance.into()), None, None, None),
+            source,
+        )
+    }
+}
+
+/// Collects fractional scroll wheel steps from high resolution wheels into whole detents.
+#[derive(Debug, Default)]
+pub struct WheelAccumulator {
+    remainder: Vec2,
+}
+
+impl WheelAccumulator {
+    /// Add `steps` and take out the whole detents collected so far, if there are any.
+    pub fn accumulate(&mut self, steps: Vec2) -> Option<Vec2> {
+        self.remainder += steps;
+        let detents = vec2(self.remainder.x.trunc(), self.remainder.y.trunc());
+        self.remainder -= detents;
+        (detents != Vec2::ZERO).then_some(detents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wheel_steps_carry_over_into_the_next_detent() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.accumulate(vec2(0.0, 0.5)), None);
+        assert_eq!(wheel.accumulate(vec2(0.0, 0.75)), Some(vec2(0.0, 1.0)));
+        assert_eq!(wheel.accumulate(vec2(0.0, 0.75)), Some(vec2(0.0, 1.0)));
+        assert_eq!(wheel.remainder, Vec2::ZERO);
+    }
+
+    #[test]
+    fn wheel_steps_in_both_directions_cancel_out() {
+        let mut wheel = WheelAccumulator::default();
+        assert_eq!(wheel.accumulate(vec2(0.5, -0.5)), None);
+        assert_eq!(wheel.accumulate(vec2(-0.5, 0.5)), None);
+        assert_eq!(wheel.accumulate(vec2(-2.25, 3.0)), Some(vec2(-2.0, 3.0)));
+        assert_eq!(wheel.remainder, vec2(-0.25, 0.0));
+    }
+
+    #[test]
+    fn scrolling_locks_to_the_axis_it_reaches_the_threshold_on() {
+        let mut lock = AxisLock::new(10.0);
+        assert_eq!(lock.apply(vec2(3.0, 6.0), false), vec2(3.0, 6.0));
+        assert_eq!(lock.apply(vec2(-1.0, -4.0), false), vec2(0.0, -4.0));
+        assert_eq!(lock.apply(vec2(20.0, 1.0), false), vec2(0.0, 1.0));
+    }
+
+    #[test]
+    fn the_lock_is_released_when_the_scroll_stops() {
+        let mut lock = AxisLock::new(10.0);
+        assert_eq!(lock.apply(vec2(0.0, 10.0), false), vec2(0.0, 10.0));
+        assert_eq!(lock.apply(vec2(5.0, 0.0), true), vec2(0.0, 0.0));
+        assert_eq!(lock.apply(vec2(5.0, 1.0), false), vec2(5.0, 1.0));
+        assert_eq!(lock.apply(vec2(5.0, 1.0), false), vec2(5.0, 0.0));
+    }
+
+    #[test]
+    fn no_threshold_never_locks() {
+        let mut lock = AxisLock::new(0.0);
+        assert_eq!(lock.apply(vec2(100.0, 1.0), false), vec2(100.0, 1.0));
+        assert_eq!(lock.apply(vec2(1.0, 1.0), false), vec2(1.0, 1.0));
+    }
+
+    fn scroll_keys(config: ScrollKeysConfig) -> ScrollKeys {
+        ScrollKeys::load(&ScrollKeysConfig {
+            enabled: true,
+            ..config
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn every_detent_types_a_key() {
+        let mut keys = scroll_keys(ScrollKeysConfig::default());
+        let down = Hotkey::parse("Down").unwrap();
+        assert_eq!(
+            keys.wheel_scrolled(vec2(0.0, 240.0)),
+            (vec![&down, &down], None)
+        );
+    }
+
+    #[test]
+    fn fractions_of_a_step_add_up_to_a_key() {
+        let mut keys = scroll_keys(ScrollKeysConfig::default());
+        let down = Hotkey::parse("Down").unwrap();
+        let step = keys.finger_distance;
+        assert_eq!(keys.finger_scrolled(vec2(0.0, step * 0.75)), (vec![], None));
+        assert_eq!(
+            keys.finger_scrolled(vec2(0.0, step * 0.75)),
+            (vec![&down], None)
+        );
+        assert_eq!(keys.accumulated, vec2(0.0, 0.5));
+    }
+
+    #[test]
+    fn turning_around_starts_a_step_over() {
+        let mut keys = scroll_keys(ScrollKeysConfig::default());
+        let up = Hotkey::parse("Up").unwrap();
+        assert_eq!(keys.wheel_scrolled(vec2(0.0, 60.0)), (vec![], None));
+        assert_eq!(keys.wheel_scrolled(vec2(0.0, -60.0)), (vec![], None));
+        assert_eq!(keys.wheel_scrolled(vec2(0.0, -60.0)), (vec![&up], None));
+    }
+
+    #[test]
+    fn directions_without_keys_keep_scrolling() {
+        let mut keys = scroll_keys(ScrollKeysConfig {
+            left: String::new(),
+            ..ScrollKeysConfig::default()
+        });
+        let down = Hotkey::parse("Down").unwrap();
+        assert_eq!(
+            keys.wheel_scrolled(vec2(-120.0, 120.0)),
+            (vec![&down], Some(vec2(-120.0, 0.0)))
+        );
+        // the end of a scroll still gets through
+        assert_eq!(keys.finger_scrolled(Vec2::ZERO), (vec![], Some(Vec2::ZERO)));
+    }
+
+    #[test]
+    fn disabled_scroll_keys_type_nothing() {
+        let mut keys = ScrollKeys::load(&ScrollKeysConfig::default()).unwrap();
+        assert_eq!(
+            keys.wheel_scrolled(vec2(0.0, 240.0)),
+            (vec![], Some(vec2(0.0, 240.0)))
+        );
+    }
+}
@@ -0,0 +1,1388 @@
+//! Reading a seat's devices with libinput on a thread of their own and turning their events into
+//! the ones sent to the frame loop.
+
+use crate::config::{
+    AccelProfile, Config, DeviceConfig, SendEvents, TabletConfig, TapButtonMap, Timestamps,
+    TouchpadConfig, WheelUnits,
+};
+use crate::control::{InjectedKey, Reply};
+use crate::error::EclipseError;
+use crate::events::{
+    next_device_id, timestamp, GestureEvent, GestureKind, GesturePhase, KeyboardInput, MouseButton,
+    MouseInput, ScrollSource, SourceEvent, SwitchEvent, SwitchKind, TabletEvent, TabletPadEvent,
+    TabletPadInput, TouchEvent, TouchPhase,
+};
+use crate::keyboard::{Action, Hotkey, InhibitedKey, KeyboardState};
+use crate::metrics::{InputKind, METRICS};
+use crate::pointer::{ButtonRepeat, DeadZone, Motion, MouseEventSender};
+use crate::scroll::{
+    scroll_axes, wheel_v120, AxisLock, ScrollKeys, ScrollMomentum, SmoothScroll, WheelAccumulator,
+};
+use crate::session::SessionActivity;
+use crate::{events, heartbeat, idle, keyboard};
+use color_eyre::eyre::Result;
+use glam::{vec2, IVec2, Vec2};
+use input::event::gesture::{
+    GestureEndEvent, GestureEventCoordinates, GestureEventTrait, GestureHoldEvent,
+    GesturePinchEvent, GesturePinchEventTrait, GestureSwipeEvent,
+};
+use input::event::keyboard::KeyboardEventTrait;
+use input::event::pointer::{Axis, PointerEventTrait};
+use input::event::switch::{Switch, SwitchEventTrait, SwitchState};
+use input::event::tablet_pad::{ButtonState, KeyState, TabletPadEventTrait};
+use input::event::tablet_tool::{ProximityState, TabletToolEventTrait, TabletToolType};
+use input::event::touch::{TouchEventPosition, TouchEventSlot, TouchEventTrait};
+use input::event::{
+    DeviceEvent, EventTrait, PointerEvent, TabletPadEvent as LibinputTabletPadEvent,
+    TabletToolEvent,
+};
+use input::{Device, DeviceCapability, Libinput, LibinputInterface, SendEventsMode};
+use libc::{O_ACCMODE, O_RDONLY, O_RDWR, O_WRONLY};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use stardust_xr_molecules::mouse::MouseEvent;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::Notify;
+use tracing::{debug, error, info, trace_span, warn};
+use xkbcommon::xkb::Keymap;
+
+/// How many devices libinput tried to open and how many of them it could.
+#[derive(Debug, Default)]
+struct OpenCounts {
+    attempted: Cell<u32>,
+    opened: Cell<u32>,
+}
+
+struct Interface {
+    counts: Rc<OpenCounts>,
+}
+
+impl LibinputInterface for Interface {
+    fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<OwnedFd, i32> {
+        self.counts.attempted.set(self.counts.attempted.get() + 1);
+        OpenOptions::new()
+            .custom_flags(flags)
+            .read(flags & O_ACCMODE == O_RDONLY || flags & O_ACCMODE == O_RDWR)
+            .write(flags & O_ACCMODE == O_WRONLY || flags & O_ACCMODE == O_RDWR)
+            .open(path)
+            .map(|file| {
+                self.counts.opened.set(self.counts.opened.get() + 1);
+                file.into()
+            })
+            .map_err(|err| {
+                // usually every device fails like this at once, which open_libinput sums up
+                if err.kind() == ErrorKind::PermissionDenied {
+                    debug!(path = %path.display(), "no permission to open input device");
+                } else {
+                    warn!(path = %path.display(), ?err, "couldn't open input device");
+                }
+                // libinput only understands errnos
+                err.raw_os_error().unwrap_or(libc::EACCES)
+            })
+    }
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        drop(File::from(fd));
+    }
+}
+
+/// Everything a seat's libinput thread sends events through, `None` for the kinds of input that
+/// aren't forwarded.
+#[derive(Clone)]
+pub struct InputSenders {
+    pub mouse: Option<Sender<MouseInput>>,
+    pub keyboard: Option<Sender<KeyboardInput>>,
+    pub touch: Option<Sender<TouchEvent>>,
+    pub tablet: Option<Sender<TabletEvent>>,
+    pub tablet_pad: Option<Sender<TabletPadEvent>>,
+    pub gesture: Option<Sender<GestureEvent>>,
+    pub switch: Option<Sender<SwitchEvent>>,
+    pub actions: Sender<HotkeyAction>,
+    /// Notified when the quit hotkey is pressed.
+    pub quit: Arc<Notify>,
+    /// Whether input's forwarded at all, with `active_session_only`.
+    pub session: SessionActivity,
+}
+
+/// A hotkey pressed on a seat, at the `time` of the key press completing it so it can be put in
+/// order with the mouse and keyboard events around it.
+#[derive(Debug)]
+pub struct HotkeyAction {
+    pub action: Action,
+    pub time: u64,
+}
+
+impl HotkeyAction {
+    fn new(action: Action) -> Self {
+        HotkeyAction {
+            action,
+            time: timestamp(),
+        }
+    }
+}
+
+impl SourceEvent for HotkeyAction {
+    fn set_source(&mut self, _seat: &str, _device: Option<u32>) {}
+    fn set_time(&mut self, time: u64) {
+        self.time = time;
+    }
+}
+
+/// Read the devices of the seat `config` is for and send their input through `senders` until the
+/// other end of `libinput_stopped` is closed. Configs sent through `config_rx` are applied and keys
+/// sent through `key_rx` typed, answering their replies, whenever `config_woken` is written to.
+pub fn read_seat(
+    mut config: Config,
+    senders: InputSenders,
+    libinput_ready_tx: tokio::sync::oneshot::Sender<Result<(), EclipseError>>,
+    libinput_stopped: UnixStream,
+    config_rx: std::sync::mpsc::Receiver<Config>,
+    key_rx: std::sync::mpsc::Receiver<(InjectedKey, tokio::sync::oneshot::Sender<Reply>)>,
+    config_woken: UnixStream,
+) {
+    let source = Rc::new(EventSource {
+        seat: config.seat.clone(),
+        device: Cell::new(None),
+        time: Cell::new(None),
+    });
+    let mut mouse_event_tx = MouseEventSender::new(SeatSender::new(&source, senders.mouse));
+    let keyboard_event_tx = SeatSender::new(&source, senders.keyboard);
+    let touch_event_tx = SeatSender::new(&source, senders.touch);
+    let tablet_event_tx = SeatSender::new(&source, senders.tablet);
+    let tablet_pad_event_tx = SeatSender::new(&source, senders.tablet_pad);
+    let gesture_event_tx = SeatSender::new(&source, senders.gesture);
+    let switch_event_tx = SeatSender::new(&source, senders.switch);
+    // never reloaded, the channels and pulse senders are only made for what was forwarded at start
+    let forward = config.forward;
+    let action_tx = SeatSender::new(&source, Some(senders.actions));
+    let quit = senders.quit;
+    let setup =
+        open_libinput(&config).and_then(|input| Ok((input, KeyboardSettings::load(&config)?)));
+    let (mut input, keyboard_settings) = match setup {
+        Ok(setup) => {
+            let _ = libinput_ready_tx.send(Ok(()));
+            setup
+        }
+        Err(err) => {
+            let _ = libinput_ready_tx.send(Err(err));
+            return;
+        }
+    };
+    let mut pollfd = PollFd::new(input.as_raw_fd(), PollFlags::POLLIN);
+    let stop_pollfd = PollFd::new(libinput_stopped.as_raw_fd(), PollFlags::POLLIN);
+    let config_pollfd = PollFd::new(config_woken.as_raw_fd(), PollFlags::POLLIN);
+
+    let mut keyboard_state = KeyboardState::new(
+        keyboard_settings.keymaps,
+        config.key_repeat.clone(),
+        keyboard_settings.hotkeys,
+        keyboard_settings.inhibited_keys,
+    );
+    let mut horizontal_scroll_modifier = keyboard_settings.horizontal_scroll_modifier;
+    let mut button_keys = keyboard_settings.button_keys;
+    let mut scroll_keys = keyboard_settings.scroll_keys;
+    // keys typed by each button held down, so the same ones are released whatever the keymap is by then
+    let mut typed_by_buttons: HashMap<(Device, u32), Vec<u32>> = HashMap::new();
+    let mut absolute_surface_size = config.absolute_surface_size;
+    let mut button_remap = config.pointer.button_remap();
+    // devices in use and their IDs, to tag events with and to apply reloaded settings to
+    let mut device_ids: HashMap<Device, u32> = HashMap::new();
+    // whether each device scrolls naturally, which Eclipse flips the scroll direction for itself
+    let mut natural_scroll: HashMap<Device, bool> = HashMap::new();
+    // mouse buttons held down on each device, to release them if it's unplugged
+    let mut held_buttons: HashMap<_, HashSet<u32>> = HashMap::new();
+    let mut wheel_accumulator = WheelAccumulator::default();
+    let mut momentum = ScrollMomentum::new(config.scroll.momentum);
+    let mut axis_lock = AxisLock::new(config.scroll.axis_lock);
+    let mut dead_zone = DeadZone::new(config.pointer.dead_zone);
+    let mut button_repeat = ButtonRepeat::new(&config.button_repeat);
+    // last position of every tablet pad ring currently being touched
+    let mut ring_positions = HashMap::new();
+    let mut disable_while_typing = DisableWhileTyping::new(&config.touchpad);
+    // libinput failing to dispatch several times in a row, to back off and eventually start over
+    let mut dispatch_failures = 0;
+    let mut dispatch_retry_delay = DISPATCH_RETRY_DELAY_MIN;
+    let session = senders.session;
+    let mut session_active = session.is_active();
+    loop {
+        // wake up in time to repeat held keys and buttons, and periodically to retry sending pending
+        // motion
+        let now = Instant::now();
+        let timeout = [
+            keyboard_state
+                .next_repeat()
+                .map(|next| next.saturating_duration_since(now)),
+            button_repeat
+                .next_repeat()
+                .map(|next| next.saturating_duration_since(now)),
+            mouse_event_tx
+                .has_pending()
+                .then_some(Duration::from_millis(1)),
+            momentum
+                .next_tick()
+                .map(|next| next.saturating_duration_since(now)),
+            // so the heartbeat can tell an idle thread from a stuck one
+            (config.heartbeat_interval > 0).then(|| Duration::from_secs(config.heartbeat_interval)),
+            session.check_interval(),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let mut pollfds = [pollfd, stop_pollfd, config_pollfd];
+        match poll(&mut pollfds, poll_timeout(timeout)) {
+            Ok(_) => (),
+            // a signal came in, like SIGHUP for a reload
+            Err(Errno::EINTR) => continue,
+            Err(err) => {
+                warn!(
+                    seat = source.seat,
+                    ?err,
+                    "couldn't poll libinput, stopping the seat"
+                );
+                break;
+            }
+        }
+        if pollfds[1]
+            .revents()
+            .is_some_and(|revents| !revents.is_empty())
+        {
+            break;
+        }
+        heartbeat::libinput_alive(&source.seat);
+        // what's sent outside of handling a libinput event isn't from any one device
+        source.device.set(None);
+        source.time.set(None);
+        // applied between libinput events, so none of them are lost or half handled
+        if pollfds[2]
+            .revents()
+            .is_some_and(|revents| !revents.is_empty())
+        {
+            let _ = (&config_woken).read(&mut [0; 64]);
+            if let Some(new_config) = config_rx.try_iter().last() {
+                match KeyboardSettings::load(&new_config) {
+                    Ok(settings) => {
+                        for event in keyboard_state.reload(
+                            settings.keymaps,
+                            new_config.key_repeat.clone(),
+                            settings.hotkeys,
+                            settings.inhibited_keys,
+                        ) {
+                            let _ = keyboard_event_tx.blocking_send(event);
+                        }
+                        for device in device_ids.keys() {
+                            device.clone().led_update(keyboard_state.leds());
+                        }
+                        horizontal_scroll_modifier = settings.horizontal_scroll_modifier;
+                        button_keys = settings.button_keys;
+                        scroll_keys = settings.scroll_keys;
+                        // the reload released every key
+                        typed_by_buttons.clear();
+                    }
+                    Err(err) => {
+                        warn!(
+                            ?err,
+                            "couldn't load the reloaded keyboard settings, keeping the old ones"
+                        );
+                    }
+                }
+                // buttons are held by their remapped code, which the new remap might not give
+                mouse_event_tx.release_buttons(
+                    std::mem::take(&mut held_buttons)
+                        .into_values()
+                        .flatten()
+                        .collect(),
+                );
+                button_remap = new_config.pointer.button_remap();
+                absolute_surface_size = new_config.absolute_surface_size;
+                disable_while_typing = DisableWhileTyping::new(&new_config.touchpad);
+                if let Some(event) = momentum.cancel() {
+                    mouse_event_tx.send(event);
+                }
+                momentum = ScrollMomentum::new(new_config.scroll.momentum);
+                axis_lock = AxisLock::new(new_config.scroll.axis_lock);
+                dead_zone = DeadZone::new(new_config.pointer.dead_zone);
+                button_repeat = ButtonRepeat::new(&new_config.button_repeat);
+                for device in device_ids.keys() {
+                    let natural = configure_accepted_device(&mut device.clone(), &new_config);
+                    natural_scroll.insert(device.clone(), natural);
+                }
+                config = new_config;
+                info!("applied reloaded config to input devices");
+            }
+            for (injected, reply) in key_rx.try_iter() {
+                if !keyboard_state.has_key(injected.key) {
+                    let _ = reply.send(Err(format!(
+                        "The keymap has no key with keycode {}",
+                        injected.key
+                    )));
+                    continue;
+                }
+                let presses = match injected.pressed {
+                    Some(pressed) => vec![pressed],
+                    None => vec![true, false],
+                };
+                for pressed in presses {
+                    let result = keyboard_state.key(None, injected.key, pressed);
+                    if let Some(leds) = result.leds {
+                        for device in device_ids.keys() {
+                            device.clone().led_update(leds);
+                        }
+                    }
+                    for event in result.inputs {
+                        let _ = keyboard_event_tx.blocking_send(event);
+                    }
+                    if let Some(action) = result.action {
+                        let _ = action_tx.blocking_send(HotkeyAction::new(action));
+                    }
+                }
+                let _ = reply.send(Ok(serde_json::Value::Null));
+            }
+        }
+        mouse_event_tx.flush_pending();
+        if let Some(event) = keyboard_state.repeat(Instant::now()) {
+            let _ = keyboard_event_tx.blocking_send(event);
+        }
+        if let Some(event) = momentum.tick(Instant::now()) {
+            mouse_event_tx.send(event);
+        }
+        if let Some(button) = button_repeat.repeat(Instant::now(), &held_buttons) {
+            mouse_event_tx.release_buttons(HashSet::from([button]));
+            mouse_event_tx.send(MouseEvent::new(None, None, None, None, Some(vec![button])));
+        }
+        if let Err(err) = input.dispatch() {
+            dispatch_failures += 1;
+            warn!(
+                seat = source.seat,
+                ?err,
+                failures = dispatch_failures,
+                "couldn't read from libinput"
+            );
+            if dispatch_failures >= DISPATCH_FAILURES_BEFORE_REOPEN {
+                match open_libinput(&config) {
+                    Ok(reopened) => {
+                        info!(seat = source.seat, "reopened libinput");
+                        // every device comes back as a new one, so nothing's held on them anymore
+                        for event in keyboard_state.release_all() {
+                            let _ = keyboard_event_tx.blocking_send(event);
+                        }
+                        mouse_event_tx.release_buttons(
+                            std::mem::take(&mut held_buttons)
+                                .into_values()
+                                .flatten()
+                                .collect(),
+                        );
+                        typed_by_buttons.clear();
+                        device_ids.clear();
+                        natural_scroll.clear();
+                        ring_positions.clear();
+                        input = reopened;
+                        pollfd = PollFd::new(input.as_raw_fd(), PollFlags::POLLIN);
+                        dispatch_failures = 0;
+                        dispatch_retry_delay = DISPATCH_RETRY_DELAY_MIN;
+                        continue;
+                    }
+                    Err(err) => error!(seat = source.seat, ?err, "couldn't reopen libinput"),
+                }
+            }
+            // don't spin on an error that isn't going away
+            std::thread::sleep(dispatch_retry_delay);
+            dispatch_retry_delay = (dispatch_retry_delay * 2).min(DISPATCH_RETRY_DELAY_MAX);
+            continue;
+        }
+        dispatch_failures = 0;
+        dispatch_retry_delay = DISPATCH_RETRY_DELAY_MIN;
+        if session_active != session.is_active() {
+            session_active = !session_active;
+            if !session_active {
+                // what's held now is let go of in another session, where Eclipse never sees it
+                for event in keyboard_state.release_all() {
+                    let _ = keyboard_event_tx.blocking_send(event);
+                }
+                mouse_event_tx.release_buttons(
+                    std::mem::take(&mut held_buttons)
+                        .into_values()
+                        .flatten()
+                        .collect(),
+                );
+                typed_by_buttons.clear();
+                if let Some(event) = momentum.cancel() {
+                    mouse_event_tx.send(event);
+                }
+            }
+        }
+        for event in &mut input {
+            let _span = trace_span!("libinput_event", device = event.device().name()).entered();
+            source.device.set(device_ids.get(&event.device()).copied());
+            source
+                .time
+                .set(event_time(&event).filter(|_| config.timestamps == Timestamps::Libinput));
+            // devices still come and go while the session's inactive, only input is thrown away
+            if !session_active && InputKind::of(&event).is_some() {
+                continue;
+            }
+            if InputKind::of(&event).is_some_and(|kind| !forward.forwards(kind)) {
+                continue;
+            }
+            if let Some(kind) = InputKind::of(&event) {
+                METRICS.received(kind);
+                idle::input_happened();
+            }
+            match event {
+                input::Event::Device(DeviceEvent::Added(added)) => {
+                    let mut device = added.device();
+                    let name = device.name().to_string();
+                    if config
+                        .devices
+                        .accepts(&name, device.id_vendor(), device.id_product())
+                    {
+                        let id = next_device_id();
+                        info!(
+                            device = name,
+                            id,
+                            capabilities = capabilities(&device),
+                            "using input device"
+                        );
+                        device_ids.insert(device.clone(), id);
+                        source.device.set(Some(id));
+                        let natural = configure_accepted_device(&mut device, &config);
+                        natural_scroll.insert(device.clone(), natural);
+                        // libinput only sends a toggle if a switch is on when it's added
+                        for kind in [SwitchKind::Lid, SwitchKind::TabletMode] {
+                            if device.switch_has_switch(libinput_switch(kind)) == Ok(true) {
+                                let _ =
+                                    switch_event_tx.blocking_send(SwitchEvent::new(kind, false));
+                            }
+                        }
+                    } else {
+                        info!(device = name, "ignoring input device");
+                        if let Err(err) =
+                            device.config_send_events_set_mode(SendEventsMode::DISABLED)
+                        {
+                            warn!(device = name, ?err, "couldn't disable input device");
+                        }
+                    }
+                }
+                input::Event::Device(DeviceEvent::Removed(removed)) => {
+                    let device = removed.device();
+                    info!(device = device.name(), "input device removed");
+                    // nothing will ever release what's still held on it otherwise
+                    for event in keyboard_state.release_device(&device) {
+                        let _ = keyboard_event_tx.blocking_send(event);
+                    }
+                    if let Some(buttons) = held_buttons.remove(&device) {
+                        mouse_event_tx.release_buttons(buttons);
+                    }
+                    typed_by_buttons.retain(|(typed_on, _), _| *typed_on != device);
+                    device_ids.remove(&device);
+                    natural_scroll.remove(&device);
+                }
+                input::Event::Keyboard(input::event::KeyboardEvent::Key(k)) => {
+                    if k.key_state() == KeyState::Pressed {
+                        disable_while_typing.key_pressed(k.key());
+                    }
+                    let result = keyboard_state.key(
+                        Some(&k.device()),
+                        k.key(),
+                        k.key_state() == KeyState::Pressed,
+                    );
+                    if let Some(leds) = result.leds {
+                        k.device().led_update(leds);
+                    }
+                    for event in result.inputs {
+                        let _ = keyboard_event_tx.blocking_send(event);
+                    }
+                    if let Some(action) = result.action {
+                        if action == Action::TogglePause {
+                            // nothing held when pausing would be released for receivers otherwise
+                            for event in keyboard_state.release_all() {
+                                let _ = keyboard_event_tx.blocking_send(event);
+                            }
+                            mouse_event_tx.release_buttons(
+                                std::mem::take(&mut held_buttons)
+                                    .into_values()
+                                    .flatten()
+                                    .collect(),
+                            );
+                            typed_by_buttons.clear();
+                        }
+                        if action == Action::Quit {
+                            // the keys are released on the way out like with Ctrl+C
+                            quit.notify_one();
+                        } else {
+                            let _ = action_tx.blocking_send(HotkeyAction::new(action));
+                        }
+                    }
+                }
+                input::Event::Pointer(PointerEvent::Button(p)) => {
+                    // remapped the same way on press and release so nothing gets stuck down
+                    let button = button_remap.get(&p.button()).copied().unwrap_or(p.button());
+                    if p.button_state() == ButtonState::Pressed {
+                        if let Some(keys) = button_keys.get(&button) {
+                            if disable_while_typing.suppresses(&p.device()) {
+                                continue;
+                            }
+                            let Some(keys) = keyboard_state.keys_for(keys) else {
+                                warn!(button, "nothing on the keymap types the button's keys");
+                                continue;
+                            };
+                            type_keys(
+                                &mut keyboard_state,
+                                &p.device(),
+                                keys.iter().copied(),
+                                true,
+                                &keyboard_event_tx,
+                                &action_tx,
+                            );
+                            typed_by_buttons.insert((p.device(), button), keys);
+                            continue;
+                        }
+                    } else if let Some(keys) = typed_by_buttons.remove(&(p.device(), button)) {
+                        type_keys(
+                            &mut keyboard_state,
+                            &p.device(),
+                            keys.into_iter().rev(),
+                            false,
+                            &keyboard_event_tx,
+                            &action_tx,
+                        );
+                        continue;
+                    }
+                    let held = held_buttons.entry(p.device()).or_default();
+                    if p.button_state() == ButtonState::Pressed {
+                        if disable_while_typing.suppresses(&p.device()) {
+                            continue;
+                        }
+                        if let Some(event) = momentum.cancel() {
+                            mouse_event_tx.send(event);
+                        }
+                        held.insert(button);
+                        button_repeat.pressed(button);
+                    } else if !held.remove(&button) {
+                        // the press was ignored, so the release has to be as well
+                        continue;
+                    } else {
+                        button_repeat.released(button);
+                    }
+                    mouse_event_tx.send(MouseEvent::new(
+                        None,
+                        None,
+                        None,
+                        (p.button_state() == ButtonState::Released).then(|| vec![button]),
+                        (p.button_state() == ButtonState::Pressed).then(|| vec![button]),
+                    ));
+                }
+                input::Event::Pointer(PointerEvent::Motion(m)) => {
+                    if disable_while_typing.suppresses(&m.device()) {
+                        continue;
+                    }
+                    let delta = config.pointer.apply(
+                        vec2(m.dx() as f32, m.dy() as f32),
+                        vec2(m.dx_unaccelerated() as f32, m.dy_unaccelerated() as f32),
+                    );
+                    if let Some(delta) = dead_zone.apply(delta, Instant::now()) {
+                        mouse_event_tx.send_motion(Motion::Relative(delta));
+                    }
+                }
+                input::Event::Pointer(PointerEvent::MotionAbsolute(m)) => {
+                    let [width, height] = absolute_surface_size;
+                    // transformed to a size of 1 it's the fraction of the device's width and height
+                    let (position, normalized) = config.absolute_pointer.map(
+                        vec2(
+                            m.absolute_x_transformed(1) as f32,
+                            m.absolute_y_transformed(1) as f32,
+                        ),
+                        vec2(width as f32, height as f32),
+                    );
+                    mouse_event_tx.send_motion(Motion::Absolute {
+                        position,
+                        normalized: Some(normalized),
+                    });
+                }
+                input::Event::Pointer(
+                    event @ (PointerEvent::ScrollContinuous(_) | PointerEvent::ScrollFinger(_)),
+                ) => {
+                    let Some(scroll) = SmoothScroll::read(
+                        &event,
+                        &config.scroll,
+                        &natural_scroll,
+                        horizontal_scroll_modifier,
+                        &keyboard_state,
+                    ) else {
+                        continue;
+                    };
+                    let distance = axis_lock.apply(scroll.distance, scroll.stopped);
+                    let (taps, distance) = scroll_keys.finger_scrolled(distance);
+                    for hotkey in taps {
+                        tap_keys(
+                            &mut keyboard_state,
+                            &scroll.device,
+                            hotkey,
+                            &keyboard_event_tx,
+                            &action_tx,
+                        );
+                    }
+                    // as when the only axis that moved has a multiplier of 0
+                    let Some(distance) = distance.filter(|d| *d != Vec2::ZERO || scroll.stopped)
+                    else {
+                        continue;
+                    };
+                    for event in momentum.scrolled(distance, scroll.stopped, scroll.source) {
+                        mouse_event_tx.send(event);
+                    }
+                }
+                input::Event::Pointer(PointerEvent::ScrollWheel(s)) => {
+                    let value = |axis| wheel_v120(&s, axis, config.scroll.legacy_wheel_step);
+                    let natural = natural_scroll.get(&s.device()).copied().unwrap_or_default();
+                    let v120 = scroll_axes(
+                        config.scroll.apply(
+                            vec2(value(Axis::Horizontal), value(Axis::Vertical)),
+                            natural,
+                        ),
+                        horizontal_scroll_modifier,
+                        &keyboard_state,
+                    );
+                    let (taps, v120) = scroll_keys.wheel_scrolled(v120);
+                    for hotkey in taps {
+                        tap_keys(
+                            &mut keyboard_state,
+                            &s.device(),
+                            hotkey,
+                            &keyboard_event_tx,
+                            &action_tx,
+                        );
+                    }
+                    // an axis with a multiplier of 0 scrolling on its own doesn't scroll at all
+                    let Some(v120) = v120.filter(|v120| *v120 != Vec2::ZERO) else {
+                        continue;
+                    };
+                    if config.scroll.wheel_units == WheelUnits::V120 {
+                        let v120 = v120.round().as_ivec2();
+                        if v120 == IVec2::ZERO {
+                            continue;
+                        }
+                        mouse_event_tx.send(MouseInput {
+                            scroll_v120: Some(v120.into()),
+                            ..MouseInput::scroll(
+                                MouseEvent::new(None, None, None, None, None),
+                                ScrollSource::Wheel,
+                            )
+                        });
+                        continue;
+                    }
+                    let steps = v120 / 120.0;
+                    let steps = if config.scroll.whole_detents {
+                        match wheel_accumulator.accumulate(steps) {
+                            Some(steps) => steps,
+                            None => continue,
+                        }
+                    } else {
+                        steps
+                    };
+                    mouse_event_tx.send(MouseInput::scroll(
+                        MouseEvent::new(None, None, Some(steps.into()), None, None),
+                        ScrollSource::Wheel,
+                    ));
+                }
+                input::Event::Touch(input::event::TouchEvent::Down(t)) => {
+                    if let Some(event) = momentum.cancel() {
+                        mouse_event_tx.send(event);
+                    }
+                    let position = Some(touch_position(&t, absolute_surface_size));
+                    let event = TouchEvent::new(t.seat_slot(), TouchPhase::Down, position);
+                    let _ = touch_event_tx.blocking_send(event);
+                }
+                input::Event::Touch(input::event::TouchEvent::Motion(t)) => {
+                    let position = Some(touch_position(&t, absolute_surface_size));
+                    let event = TouchEvent::new(t.seat_slot(), TouchPhase::Motion, position);
+                    let _ = touch_event_tx.blocking_send(event);
+                }
+                input::Event::Touch(input::event::TouchEvent::Up(t)) => {
+                    let event = TouchEvent::new(t.seat_slot(), TouchPhase::Up, None);
+                    let _ = touch_event_tx.blocking_send(event);
+                }
+                input::Event::Touch(input::event::TouchEvent::Cancel(t)) => {
+                    let event = TouchEvent::new(t.seat_slot(), TouchPhase::Cancel, None);
+                    let _ = touch_event_tx.blocking_send(event);
+                }
+                input::Event::Tablet(t) => {
+                    let in_proximity = match &t {
+                        TabletToolEvent::Proximity(p) => p.proximity_state() == ProximityState::In,
+                        _ => true,
+                    };
+                    let _ = tablet_event_tx.blocking_send(tablet_event(
+                        &t,
+                        in_proximity,
+                        &config.tablet,
+                        absolute_surface_size,
+                    ));
+                }
+                input::Event::TabletPad(p) => {
+                    let input = match &p {
+                        LibinputTabletPadEvent::Button(b) => TabletPadInput::Button {
+                            number: b.button_number(),
+                            pressed: b.button_state() == ButtonState::Pressed,
+                        },
+                        LibinputTabletPadEvent::Ring(r) => {
+                            // libinput reports -1 once the finger is lifted
+                            let position = (r.position() >= 0.0).then(|| r.position());
+                            let last = match position {
+                                Some(position) => ring_positions.insert(r.number(), position),
+                                None => ring_positions.remove(&r.number()),
+                            };
+                            TabletPadInput::Ring {
+                                number: r.number(),
+                                position: position.map(|p| p as f32),
+                                delta: ring_delta(last, position) as f32,
+                            }
+                        }
+                        LibinputTabletPadEvent::Strip(s) => TabletPadInput::Strip {
+                            number: s.number(),
+                            position: (s.position() >= 0.0).then(|| s.position() as f32),
+                        },
+                        _ => continue,
+                    };
+                    let event = TabletPadEvent::new(input, p.mode_group().index(), p.mode());
+                    let _ = tablet_pad_event_tx.blocking_send(event);
+                }
+                input::Event::Gesture(g) => {
+                    if let Some(event) = gesture_event(&g) {
+                        // fingers coming back down on the touchpad catch the scrolling
+                        if event.phase == GesturePhase::Begin {
+                            if let Some(event) = momentum.cancel() {
+                                mouse_event_tx.send(event);
+                            }
+                        }
+                        let _ = gesture_event_tx.blocking_send(event);
+                    }
+                }
+                input::Event::Switch(input::event::SwitchEvent::Toggle(s)) => {
+                    let kind = match s.switch() {
+                        Some(Switch::Lid) => SwitchKind::Lid,
+                        Some(Switch::TabletMode) => SwitchKind::TabletMode,
+                        _ => continue,
+                    };
+                    let on = s.switch_state() == SwitchState::On;
+                    let _ = switch_event_tx.blocking_send(SwitchEvent::new(kind, on));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    heartbeat::libinput_stopped(&source.seat);
+    // don't leave anything stuck down in whatever had focus
+    source.device.set(None);
+    source.time.set(None);
+    for event in keyboard_state.release_all() {
+        let _ = keyboard_event_tx.blocking_send(event);
+    }
+    mouse_event_tx.release_buttons(held_buttons.into_values().flatten().collect());
+    mouse_event_tx.flush_pending();
+}
+
+/// How many times in a row libinput can fail to dispatch before it's opened again from scratch.
+const DISPATCH_FAILURES_BEFORE_REOPEN: u32 = 5;
+
+const DISPATCH_RETRY_DELAY_MIN: Duration = Duration::from_millis(10);
+
+const DISPATCH_RETRY_DELAY_MAX: Duration = Duration::from_secs(1);
+
+/// Create a libinput context taking its devices from the configured seat, or from the configured
+/// device paths without udev if there are any.
+fn open_libinput(config: &Config) -> Result<Libinput, EclipseError> {
+    let counts = Rc::new(OpenCounts::default());
+    let interface = Interface {
+        counts: counts.clone(),
+    };
+    let seat = &config.seat;
+    let input = if config.device_paths.is_empty() {
+        let mut input = Libinput::new_with_udev(interface);
+        input
+            .udev_assign_seat(seat)
+            .map_err(|_| EclipseError::Libinput { seat: seat.clone() })?;
+        info!(seat, "using input devices from seat");
+        input
+    } else {
+        let mut input = Libinput::new_from_path(interface);
+        for path in &config.device_paths {
+            let path = path.display().to_string();
+            if input.path_add_device(&path).is_none() {
+                warn!(path, "couldn't add input device");
+            }
+        }
+        info!(paths = ?config.device_paths, "using input devices without udev");
+        input
+    };
+    // libinput opens every device while assigning the seat or adding the paths
+    match (counts.attempted.get(), counts.opened.get()) {
+        (0, _) => warn!(
+            seat,
+            "no input devices on the seat, check that it's the right one and that udev is running"
+        ),
+        (attempted, 0) => warn!(
+            attempted,
+            "couldn't open any input devices, add the user to the input group and log in again"
+        ),
+        _ => (),
+    }
+    Ok(input)
+}
+
+/// Print every input device libinput finds along with what it can do.
+pub fn list_devices(config: &Config) -> Result<()> {
+    let seats = config.seat_configs();
+    for seat_config in &seats {
+        if seats.len() > 1 {
+            println!("{}:", seat_config.seat);
+        }
+        let mut input = open_libinput(seat_config)?;
+        input.dispatch()?;
+        for event in &mut input {
+            let input::Event::Device(DeviceEvent::Added(added)) = event else {
+                continue;
+            };
+            let device = added.device();
+            println!(
+                "{} ({}) [{:04x}:{:04x}]: {}",
+                device.name(),
+                device.sysname(),
+                device.id_vendor(),
+                device.id_product(),
+                capabilities(&device)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Apply the pointer, scroll and touchpad settings from `config` to a device that's being used.
+///
+/// Returns whether the device scrolls naturally: never with `--no-natural-scroll`, otherwise as its
+/// config says or else if natural scrolling is on globally or libinput scrolls it naturally by
+/// default.
+fn configure_accepted_device(device: &mut Device, config: &Config) -> bool {
+    let device_config = config
+        .device_config(
+            device.name(),
+            device.sysname(),
+            device.id_vendor(),
+            device.id_product(),
+        )
+        .copied()
+        .unwrap_or_default();
+    let mut touchpad = config.touchpad;
+    if let Some(tap_to_click) = device_config.tap_to_click {
+        touchpad.tap_to_click = tap_to_click;
+    }
+    configure_touchpad(device, &touchpad);
+    configure_device(device, &device_config);
+    !config.scroll.traditional
+        && device_config.natural_scroll.unwrap_or(
+            config.scroll.natural || device.config_scroll_default_natural_scroll_enabled(),
+        )
+}
+
+/// Apply the tapping settings to `device` if it's a touchpad.
+fn configure_touchpad(device: &mut Device, config: &TouchpadConfig) {
+    if device.config_tap_finger_count() == 0 {
+        return;
+    }
+    let button_map = match config.tap_button_map {
+        TapButtonMap::Lrm => input::TapButtonMap::LeftRightMiddle,
+        TapButtonMap::Lmr => input::TapButtonMap::LeftMiddleRight,
+    };
+    let results = [
+        (
+            "tap to click",
+            device.config_tap_set_enabled(config.tap_to_click),
+        ),
+        (
+            "tap and drag",
+            device.config_tap_set_drag_enabled(config.tap_and_drag),
+        ),
+        (
+            "tap button map",
+            device.config_tap_set_button_map(button_map),
+        ),
+    ];
+    for (setting, result) in results {
+        if let Err(err) = result {
+            warn!(
+                device = device.name(),
+                setting,
+                ?err,
+                "couldn't configure touchpad"
+            );
+        }
+    }
+}
+
+/// Apply the libinput settings from a device's config block.
+fn configure_device(device: &mut Device, config: &DeviceConfig) {
+    let mut results = Vec::new();
+    if let Some(speed) = config.accel_speed {
+        results.push(("accel speed", device.config_accel_set_speed(speed)));
+    }
+    if let Some(profile) = config.accel_profile {
+        let profile = match profile {
+            AccelProfile::Flat => input::AccelProfile::Flat,
+            AccelProfile::Adaptive => input::AccelProfile::Adaptive,
+        };
+        results.push(("accel profile", device.config_accel_set_profile(profile)));
+    }
+    // Eclipse flips the direction of each device's scrolling itself, so libinput always sends it
+    // the traditional way around
+    if device.config_scroll_has_natural_scroll() {
+        results.push((
+            "natural scroll",
+            device.config_scroll_set_natural_scroll_enabled(false),
+        ));
+    }
+    if let Some(send_events) = config.send_events {
+        let mode = match send_events {
+            SendEvents::Enabled => SendEventsMode::ENABLED,
+            SendEvents::Disabled => SendEventsMode::DISABLED,
+            SendEvents::DisabledOnExternalMouse => SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE,
+        };
+        results.push(("send events", device.config_send_events_set_mode(mode)));
+    }
+    for (setting, result) in results {
+        if let Err(err) = result {
+            warn!(
+                device = device.name(),
+                setting,
+                ?err,
+                "couldn't configure input device"
+            );
+        }
+    }
+}
+
+/// Comma separated list of the kinds of input `device` provides.
+fn capabilities(device: &Device) -> String {
+    [
+        DeviceCapability::Keyboard,
+        DeviceCapability::Pointer,
+        DeviceCapability::Touch,
+        DeviceCapability::TabletTool,
+        DeviceCapability::TabletPad,
+        DeviceCapability::Gesture,
+        DeviceCapability::Switch,
+    ]
+    .into_iter()
+    .filter(|capability| device.has_capability(*capability))
+    .map(|capability| format!("{capability:?}"))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// The libinput switch a `SwitchKind` stands for.
+fn libinput_switch(kind: SwitchKind) -> Switch {
+    match kind {
+        SwitchKind::Lid => Switch::Lid,
+        SwitchKind::TabletMode => Switch::TabletMode,
+    }
+}
+
+/// Convert a timeout to milliseconds for `poll`, rounding up so it never wakes up too early.
+pub fn poll_timeout(timeout: Option<Duration>) -> i32 {
+    timeout.map_or(-1, |timeout| {
+        timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+    })
+}
+
+fn touch_position(
+    touch: &impl TouchEventPosition,
+    [width, height]: [u32; 2],
+) -> mint::Vector2<f32> {
+    vec2(
+        touch.x_transformed(width) as f32,
+        touch.y_transformed(height) as f32,
+    )
+    .into()
+}
+
+fn tablet_event(
+    tool: &(impl TabletToolEventTrait + EventTrait),
+    in_proximity: bool,
+    area: &TabletConfig,
+    [width, height]: [u32; 2],
+) -> TabletEvent {
+    let surface_size = vec2(width as f32, height as f32);
+    let tablet_size = tool
+        .device()
+        .size()
+        .map(|(width, height)| vec2(width as f32, height as f32));
+    // transformed to a size of 1 it's the fraction of the tablet's width and height
+    let on_tablet = vec2(tool.x_transformed(1) as f32, tool.y_transformed(1) as f32);
+    let (in_area, out_of_area) = area.map(on_tablet, tablet_size, surface_size);
+    let physical_tool = tool.tool();
+    let tool_type = physical_tool
+        .tool_type()
+        .and_then(|tool_type| match tool_type {
+            TabletToolType::Pen => Some(events::TabletToolType::Pen),
+            TabletToolType::Eraser => Some(events::TabletToolType::Eraser),
+            TabletToolType::Brush => Some(events::TabletToolType::Brush),
+            TabletToolType::Pencil => Some(events::TabletToolType::Pencil),
+            TabletToolType::Airbrush => Some(events::TabletToolType::Airbrush),
+            TabletToolType::Mouse => Some(events::TabletToolType::Mouse),
+            TabletToolType::Lens => Some(events::TabletToolType::Lens),
+            TabletToolType::Totem => Some(events::TabletToolType::Totem),
+            _ => None,
+        });
+    TabletEvent {
+        tool_type,
+        serial: physical_tool.serial(),
+        tool_id: physical_tool.tool_id(),
+        out_of_area,
+        ..TabletEvent::new(
+            (in_area * surface_size).into(),
+            tool.pressure() as f32,
+            vec2(tool.tilt_x() as f32, tool.tilt_y() as f32).into(),
+            in_proximity,
+        )
+    }
+}
+
+fn gesture_event(gesture: &input::event::GestureEvent) -> Option<GestureEvent> {
+    let finger_count = gesture.finger_count() as u32;
+    let end_phase = |end: &dyn GestureEndEvent| {
+        if end.cancelled() {
+            GesturePhase::Cancel
+        } else {
+            GesturePhase::End
+        }
+    };
+    let event = match gesture {
+        input::event::GestureEvent::Swipe(swipe) => {
+            let kind = GestureKind::Swipe;
+            match swipe {
+                GestureSwipeEvent::Begin(_) => {
+                    GestureEvent::new(kind, GesturePhase::Begin, finger_count)
+                }
+                GestureSwipeEvent::Update(update) => GestureEvent {
+                    delta: Some(vec2(update.dx() as f32, update.dy() as f32).into()),
+                    ..GestureEvent::new(kind, GesturePhase::Update, finger_count)
+                },
+                GestureSwipeEvent::End(end) => {
+                    GestureEvent::new(kind, end_phase(end), finger_count)
+                }
+                _ => return None,
+            }
+        }
+        input::event::GestureEvent::Pinch(pinch) => {
+            let kind = GestureKind::Pinch;
+            let phase = match pinch {
+                GesturePinchEvent::Begin(_) => GesturePhase::Begin,
+                GesturePinchEvent::Update(_) => GesturePhase::Update,
+                GesturePinchEvent::End(end) => end_phase(end),
+                _ => return None,
+            };
+            let mut event = GestureEvent {
+                scale: Some(pinch.scale() as f32),
+                ..GestureEvent::new(kind, phase, finger_count)
+            };
+            if let GesturePinchEvent::Update(update) = pinch {
+                event.delta = Some(vec2(update.dx() as f32, update.dy() as f32).into());
+                event.rotation_delta = Some(update.angle_delta() as f32);
+            }
+            event
+        }
+        input::event::GestureEvent::Hold(hold) => {
+            let phase = match hold {
+                GestureHoldEvent::Begin(_) => GesturePhase::Begin,
+                // a hold cancels when the fingers lift too early or start moving
+                GestureHoldEvent::End(end) => end_phase(end),
+                _ => return None,
+            };
+            GestureEvent::new(GestureKind::Hold, phase, finger_count)
+        }
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Degrees a ring turned between two positions, taking the shortest way around.
+fn ring_delta(last: Option<f64>, position: Option<f64>) -> f64 {
+    let (Some(last), Some(position)) = (last, position) else {
+        return 0.0;
+    };
+    (position - last + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// Modifier keys, which don't count as typing so modifier+click still works straight away.
+const MODIFIER_KEYS: [u32; 8] = [
+    input_event_codes::KEY_LEFTCTRL!(),
+    input_event_codes::KEY_RIGHTCTRL!(),
+    input_event_codes::KEY_LEFTSHIFT!(),
+    input_event_codes::KEY_RIGHTSHIFT!(),
+    input_event_codes::KEY_LEFTALT!(),
+    input_event_codes::KEY_RIGHTALT!(),
+    input_event_codes::KEY_LEFTMETA!(),
+    input_event_codes::KEY_RIGHTMETA!(),
+];
+
+/// Press or release `keys` in turn on `device` as if it were a keyboard, sending what they type.
+fn type_keys(
+    keyboard_state: &mut KeyboardState,
+    device: &Device,
+    keys: impl IntoIterator<Item = u32>,
+    pressed: bool,
+    keyboard_event_tx: &SeatSender<KeyboardInput>,
+    action_tx: &SeatSender<HotkeyAction>,
+) {
+    for key in keys {
+        let result = keyboard_state.key(Some(device), key, pressed);
+        for event in result.inputs {
+            let _ = keyboard_event_tx.blocking_send(event);
+        }
+        if let Some(action) = result.action {
+            let _ = action_tx.blocking_send(HotkeyAction::new(action));
+        }
+    }
+}
+
+/// Press and release `hotkey`'s keys on `device`, for scrolling that types keys instead.
+fn tap_keys(
+    keyboard_state: &mut KeyboardState,
+    device: &Device,
+    hotkey: &Hotkey,
+    keyboard_event_tx: &SeatSender<KeyboardInput>,
+    action_tx: &SeatSender<HotkeyAction>,
+) {
+    let Some(keys) = keyboard_state.keys_for(hotkey) else {
+        warn!("nothing on the keymap types the scroll keys");
+        return;
+    };
+    type_keys(
+        keyboard_state,
+        device,
+        keys.iter().copied(),
+        true,
+        keyboard_event_tx,
+        action_tx,
+    );
+    type_keys(
+        keyboard_state,
+        device,
+        keys.into_iter().rev(),
+        false,
+        keyboard_event_tx,
+        action_tx,
+    );
+}
+
+/// What the libinput thread's keyboard handling takes from the config.
+struct KeyboardSettings {
+    keymaps: Vec<Keymap>,
+    hotkeys: Vec<(Hotkey, Action)>,
+    horizontal_scroll_modifier: Option<&'static str>,
+    /// What mouse buttons type instead of being pressed, by evdev code.
+    button_keys: HashMap<u32, Hotkey>,
+    scroll_keys: ScrollKeys,
+    inhibited_keys: Vec<InhibitedKey>,
+}
+
+impl KeyboardSettings {
+    fn load(config: &Config) -> Result<Self, EclipseError> {
+        let keymaps = std::iter::once(&config.keymap)
+            .chain(&config.extra_keymaps)
+            .map(keyboard::load_keymap)
+            .collect::<Result<_, _>>()?;
+        let hotkeys = keyboard::load_hotkeys(&config.hotkeys)?;
+        let horizontal_scroll_modifier = (!config.scroll.horizontal_modifier.is_empty())
+            .then(|| keyboard::modifier(&config.scroll.horizontal_modifier))
+            .transpose()
+            .map_err(|err| err.in_setting("scroll.horizontal_modifier"))?;
+        let button_keys = config
+            .button_keys
+            .iter()
+            .map(|(button, keys)| {
+                let code = MouseButton::parse_code(button)
+                    .ok_or_else(|| EclipseError::UnknownButton(button.clone()))?;
+                let keys = Hotkey::parse(keys).map_err(|err| err.in_setting("button_keys"))?;
+                Ok((code, keys))
+            })
+            .collect::<Result<_, EclipseError>>()?;
+        let scroll_keys = ScrollKeys::load(&config.scroll.keys)?;
+        let inhibited_keys = config
+            .inhibited_keys
+            .iter()
+            .map(|key| InhibitedKey::parse(key))
+            .collect::<Result<_, _>>()?;
+        Ok(KeyboardSettings {
+            keymaps,
+            hotkeys,
+            horizontal_scroll_modifier,
+            button_keys,
+            scroll_keys,
+            inhibited_keys,
+        })
+    }
+}
+
+/// Hands reloaded configs and injected keys to the libinput threads, waking them up to handle them.
+#[derive(Default)]
+pub struct SeatControl {
+    pub seats: Vec<SeatChannels>,
+}
+
+impl SeatControl {
+    pub fn send(&self, config: &Config) {
+        for seat in &self.seats {
+            if seat.config_tx.send(config.for_seat(&seat.seat)).is_ok() {
+                let _ = (&seat.wake).write_all(&[0]);
+            }
+        }
+    }
+
+    /// Type `key` on the thread of its seat, the main seat's if it doesn't say. The thread answers
+    /// `reply` once it's checked the key against its keymap.
+    pub fn inject_key(&self, key: InjectedKey, reply: tokio::sync::oneshot::Sender<Reply>) {
+        let seat = match &key.seat {
+            Some(seat) => self.seats.iter().find(|channels| channels.seat == *seat),
+            None => self.seats.first(),
+        };
+        let Some(seat) = seat else {
+            let _ = reply.send(Err(match &key.seat {
+                Some(seat) => format!("Seat {seat:?} isn't read"),
+                None => "No seat is read while replaying".to_string(),
+            }));
+            return;
+        };
+        if let Err(std::sync::mpsc::SendError((_, reply))) = seat.key_tx.send((key, reply)) {
+            let _ = reply.send(Err("The seat's libinput thread stopped".to_string()));
+            return;
+        }
+        let _ = (&seat.wake).write_all(&[0]);
+    }
+}
+
+/// The channels to a seat's libinput thread and what wakes it up.
+pub struct SeatChannels {
+    pub seat: String,
+    pub config_tx: std::sync::mpsc::Sender<Config>,
+    pub key_tx: std::sync::mpsc::Sender<(InjectedKey, tokio::sync::oneshot::Sender<Reply>)>,
+    pub wake: UnixStream,
+}
+
+/// Where the events a libinput thread is sending right now come from.
+pub struct EventSource {
+    seat: String,
+    /// The ID of the device behind the libinput event being handled, `None` between events.
+    device: Cell<Option<u32>>,
+    /// When libinput says the input behind the event being handled happened, `None` between events
+    /// and when events are timestamped as they're received instead.
+    time: Cell<Option<u64>>,
+}
+
+/// When libinput says an input event happened, `None` for device and other bookkeeping events.
+fn event_time(event: &input::Event) -> Option<u64> {
+    match event {
+        input::Event::Keyboard(event) => Some(event.time_usec()),
+        input::Event::Pointer(event) => Some(event.time_usec()),
+        input::Event::Touch(event) => Some(event.time_usec()),
+        input::Event::Tablet(event) => Some(event.time_usec()),
+        input::Event::TabletPad(event) => Some(event.time_usec()),
+        input::Event::Gesture(event) => Some(event.time_usec()),
+        input::Event::Switch(event) => Some(event.time_usec()),
+        _ => None,
+    }
+}
+
+/// Sends events from a seat's libinput thread, marking them with the seat and device on the way.
+pub struct SeatSender<E> {
+    source: Rc<EventSource>,
+    /// `None` if this kind of input isn't forwarded, which quietly drops what's sent.
+    tx: Option<Sender<E>>,
+}
+
+impl<E: SourceEvent> SeatSender<E> {
+    fn new(source: &Rc<EventSource>, tx: Option<Sender<E>>) -> Self {
+        SeatSender {
+            source: source.clone(),
+            tx,
+        }
+    }
+
+    pub fn device(&self) -> Option<u32> {
+        self.source.device.get()
+    }
+
+    pub fn blocking_send(&self, event: E) -> Result<(), SendError<E>> {
+        self.blocking_send_from(self.device(), event)
+    }
+
+    /// Send `event` as coming from `device` rather than the current one.
+    pub fn blocking_send_from(
+        &self,
+        device: Option<u32>,
+        mut event: E,
+    ) -> Result<(), SendError<E>> {
+        let Some(tx) = &self.tx else {
+            return Ok(());
+        };
+        self.mark(&mut event, device);
+        tx.blocking_send(event)
+    }
+
+    pub fn try_send_from(&self, device: Option<u32>, mut event: E) -> Result<(), TrySendError<E>> {
+        let Some(tx) = &self.tx else {
+            return Ok(());
+        };
+        self.mark(&mut event, device);
+        tx.try_send(event)
+    }
+
+    fn mark(&self, event: &mut E, device: Option<u32>) {
+        event.set_source(&self.source.seat, device);
+        if let Some(time) = self.source.time.get() {
+            event.set_time(time);
+        }
+    }
+}
+
+/// Ignores touchpads for a moment after every key press.
+struct DisableWhileTyping {
+    /// How long touchpads stay ignored, `None` if they never are.
+    timeout: Option<Duration>,
+    last_key_press: Option<Instant>,
+}
+
+impl DisableWhileTyping {
+    fn new(config: &TouchpadConfig) -> Self {
+        DisableWhileTyping {
+            timeout: config
+                .disable_while_typing
+                .then(|| Duration::from_millis(config.typing_timeout)),
+            last_key_press: None,
+        }
+    }
+
+    fn key_pressed(&mut self, key: u32) {
+        if !MODIFIER_KEYS.contains(&key) {
+            self.last_key_press = Some(Instant::now());
+        }
+    }
+
+    /// Whether input from `device` should be ignored right now.
+    fn suppresses(&self, device: &Device) -> bool {
+        let (Some(timeout), Some(last_key_press)) = (self.timeout, self.last_key_press) else {
+            return false;
+        };
+        // only touchpads can tap
+        last_key_press.elapsed() < timeout && device.config_tap_finger_count() > 0
+    }
+}
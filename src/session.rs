@@ -0,0 +1,97 @@
+//! Whether the logind session Eclipse runs in is the active one on its seat, so input isn't
+//! forwarded while someone else is using the seat from another session.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+/// How often logind's state for the session is read again, which is how long input can still get
+/// out after the session turns inactive.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Whether the session is active, shared by the threads forwarding input. Reading it is just an
+/// atomic load, so it can be checked for every event.
+#[derive(Debug, Clone)]
+pub struct SessionActivity {
+    active: Arc<AtomicBool>,
+    followed: bool,
+}
+impl SessionActivity {
+    /// Always active, for forwarding input whatever session is active.
+    pub fn always() -> Self {
+        SessionActivity {
+            active: Arc::new(AtomicBool::new(true)),
+            followed: false,
+        }
+    }
+
+    /// Keep up to date with the session Eclipse was started in, from `XDG_SESSION_ID`, until every
+    /// clone is dropped.
+    ///
+    /// This reads the state logind keeps under /run/systemd/sessions, the same as
+    /// `sd_session_is_active` does. Outside of a logind session input is always forwarded.
+    pub fn follow() -> Self {
+        let mut activity = SessionActivity::always();
+        let Ok(session) = std::env::var("XDG_SESSION_ID") else {
+            info!("not running in a logind session, forwarding input whatever session is active");
+            return activity;
+        };
+        let path = Path::new("/run/systemd/sessions").join(&session);
+        // before any input is read, so none from while the session is inactive gets through
+        if !update(&session, &path, &activity.active) {
+            return activity;
+        }
+        activity.followed = true;
+        let active = Arc::downgrade(&activity.active);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                let Some(active) = active.upgrade() else {
+                    break;
+                };
+                if !update(&session, &path, &active) {
+                    break;
+                }
+            }
+        });
+        activity
+    }
+
+    /// How often to look at `is_active` while there's no input, to let go of what's held soon after
+    /// the session turns inactive. `None` if it never changes.
+    pub fn check_interval(&self) -> Option<Duration> {
+        self.followed.then_some(POLL_INTERVAL)
+    }
+
+    /// Whether the session is active, or Eclipse isn't following the session's state.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+/// Read whether `session` is active into `active`, `false` if it can't be read, which stops
+/// following it.
+fn update(session: &str, path: &Path, active: &AtomicBool) -> bool {
+    let state = match std::fs::read_to_string(path) {
+        Ok(state) => state,
+        Err(err) => {
+            warn!(
+                session,
+                ?err,
+                "couldn't read the session's state from logind, forwarding input regardless"
+            );
+            active.store(true, Ordering::Relaxed);
+            return false;
+        }
+    };
+    let is_active = state.lines().any(|line| line == "ACTIVE=1");
+    if active.swap(is_active, Ordering::Relaxed) != is_active {
+        info!(session, active = is_active, "session activity changed");
+    }
+    true
+}